@@ -1,8 +1,169 @@
 extern crate neutron_star_constants;
-use ring::digest::{Context, SHA256};
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+use crate::crypto::sha256;
+use crate::encoding::*;
 use rand::Rng;
 use neutron_star_constants::*;
 
+/*
+## Address text formats
+
+Addresses are written using bech32, with a human readable part giving the kind of address and its version:
+
+* Full addresses use "n" followed by the version, ie "n2" for version 2
+* Short addresses use "ns" followed by the version, ie "ns2" for version 2
+
+The version is the number of the address's AddressKind, written in decimal without leading zeros, so each address has exactly one canonical text form.
+Base58check is also supported for short addresses of the fixed length kinds, so that pubkeyhash and scripthash addresses can be written the same way as Qtum addresses.
+The prefix byte gives both the kind and the network:
+
+| Kind        | Mainnet     | Testnet     |
+|-------------|-------------|-------------|
+| PubKeyHash  | 0x3a ("Q")  | 0x78 ("q")  |
+| X86Contract | 0x35 ("N")  | 0x70 ("n")  |
+| ScriptHash  | 0x32 ("M")  | 0x6e ("m")  |
+
+Other kinds have no base58check prefix, so can only be written using bech32.
+*/
+
+/// The amount of data in a short address
+pub const SHORT_ADDRESS_SIZE: usize = 20;
+
+/// The kind of an address, which is given by its version and determines how coins sent to it are spent, or which VM executes it
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum AddressKind{
+    /// Version 1. The hash160 of a public key
+    PubKeyHash,
+    /// Version 2. A smart contract executed by the x86 VM
+    X86Contract,
+    /// Version 3. The hash160 of a script
+    ScriptHash,
+    /// Any other version, such as a smart contract of a VM which has not been added yet.
+    /// This should not be used with the versions of the kinds above
+    Other(u32)
+}
+
+impl AddressKind{
+    pub fn from_version(version: u32) -> AddressKind{
+        match version{
+            1 => AddressKind::PubKeyHash,
+            2 => AddressKind::X86Contract,
+            3 => AddressKind::ScriptHash,
+            v => AddressKind::Other(v)
+        }
+    }
+    pub fn version(&self) -> u32{
+        match self{
+            AddressKind::PubKeyHash => 1,
+            AddressKind::X86Contract => 2,
+            AddressKind::ScriptHash => 3,
+            AddressKind::Other(v) => *v
+        }
+    }
+    /// The amount of data which full addresses of this kind must have, or None if it is not known
+    pub fn data_length(&self) -> Option<usize>{
+        match self{
+            AddressKind::PubKeyHash | AddressKind::X86Contract | AddressKind::ScriptHash => Some(SHORT_ADDRESS_SIZE),
+            AddressKind::Other(_) => None
+        }
+    }
+    /// Returns true if addresses of this kind are smart contracts which can be deployed and called
+    pub fn is_contract(&self) -> bool{
+        match self{
+            AddressKind::X86Contract => true,
+            _ => false
+        }
+    }
+}
+
+/// The base58check prefix of Qtum mainnet pubkeyhash addresses
+pub const QTUM_MAINNET_PUBKEYHASH_PREFIX: u8 = 0x3a;
+/// The base58check prefix of Qtum testnet and regtest pubkeyhash addresses
+pub const QTUM_TESTNET_PUBKEYHASH_PREFIX: u8 = 0x78;
+/// The base58check prefix of Qtum mainnet scripthash addresses
+pub const QTUM_MAINNET_SCRIPTHASH_PREFIX: u8 = 0x32;
+/// The base58check prefix of Qtum testnet and regtest scripthash addresses
+pub const QTUM_TESTNET_SCRIPTHASH_PREFIX: u8 = 0x6e;
+/// The base58check prefix of mainnet x86 contract addresses
+pub const MAINNET_X86CONTRACT_PREFIX: u8 = 0x35;
+/// The base58check prefix of testnet and regtest x86 contract addresses
+pub const TESTNET_X86CONTRACT_PREFIX: u8 = 0x70;
+
+/// The network whose base58check prefixes an address is written with
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Network{
+    Mainnet,
+    /// Testnet and regtest, which share their prefixes
+    Testnet
+}
+
+/// The base58check prefix of a kind of address on the given network, or None if the kind can not be written using base58check
+pub fn base58check_prefix(kind: AddressKind, network: Network) -> Option<u8>{
+    match (kind, network){
+        (AddressKind::PubKeyHash, Network::Mainnet) => Some(QTUM_MAINNET_PUBKEYHASH_PREFIX),
+        (AddressKind::PubKeyHash, Network::Testnet) => Some(QTUM_TESTNET_PUBKEYHASH_PREFIX),
+        (AddressKind::X86Contract, Network::Mainnet) => Some(MAINNET_X86CONTRACT_PREFIX),
+        (AddressKind::X86Contract, Network::Testnet) => Some(TESTNET_X86CONTRACT_PREFIX),
+        (AddressKind::ScriptHash, Network::Mainnet) => Some(QTUM_MAINNET_SCRIPTHASH_PREFIX),
+        (AddressKind::ScriptHash, Network::Testnet) => Some(QTUM_TESTNET_SCRIPTHASH_PREFIX),
+        (AddressKind::Other(_), _) => None
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AddressParseError{
+    Encoding(DecodeError),
+    /// The human readable part or base58check prefix is not that of the expected kind of address
+    InvalidPrefix(String),
+    /// An address whose amount of data is not valid for its kind, or a short address which does not contain exactly 20 bytes of data
+    InvalidLength,
+    /// The version of an address which has no base58check prefix
+    UnsupportedVersion(u32)
+}
+
+impl fmt::Display for AddressParseError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            AddressParseError::Encoding(e) => {
+                write!(f, "Invalid address encoding: {}", e)
+            },
+            AddressParseError::InvalidPrefix(p) => {
+                write!(f, "Invalid address prefix: {}", p)
+            },
+            AddressParseError::InvalidLength => {
+                write!(f, "Invalid address length")
+            },
+            AddressParseError::UnsupportedVersion(v) => {
+                write!(f, "Addresses of version {} can not be written using base58check", v)
+            }
+        }
+    }
+}
+
+impl error::Error for AddressParseError{
+}
+
+impl From<DecodeError> for AddressParseError{
+    fn from(e: DecodeError) -> AddressParseError{
+        AddressParseError::Encoding(e)
+    }
+}
+
+/// Parses the version following a prefix within a bech32 human readable part
+fn parse_prefixed_version(hrp: &str, prefix: &str) -> Result<u32, AddressParseError>{
+    if hrp.starts_with(prefix){
+        let digits = &hrp[prefix.len()..];
+        if let Ok(version) = digits.parse::<u32>(){
+            if version.to_string() == digits{
+                return Ok(version);
+            }
+        }
+    }
+    Err(AddressParseError::InvalidPrefix(hrp.to_string()))
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Default, Hash)]
 /// NeutronAddress is a full dynamic length address. 
 /// Due to it being dynamic length it is inconvenient to use, but is required for sending coins to an address
@@ -14,27 +175,356 @@ pub struct NeutronAddress{
 }
 
 impl NeutronAddress{
-	/// Converts a full address into a short address
-    pub fn to_short_address(&self) -> NeutronShortAddress{
-		// this needs an exception for data sizes equal to or smaller than 20 bytes??
-        // ie, a contract address should be valid as a short address and full address because it's data is always 20 bytes
-        let mut context = Context::new(&SHA256);
-        context.update(&self.data[0..]);
-        let d = context.finish();
-        let mut data: [u8; 20] = Default::default();
-        data.copy_from_slice(&d.as_ref()[0..20]);
-        NeutronShortAddress{
-            version: self.version,
+    /// Creates an address of the given kind, checking that the data has the length required by that kind
+    pub fn new(kind: AddressKind, data: Vec<u8>) -> Result<NeutronAddress, AddressParseError>{
+        let address = NeutronAddress{
+            version: kind.version(),
             data: data
+        };
+        address.validate()?;
+        Ok(address)
+    }
+    pub fn kind(&self) -> AddressKind{
+        AddressKind::from_version(self.version)
+    }
+    /// Checks that the address has the amount of data required by its kind
+    pub fn validate(&self) -> Result<(), AddressParseError>{
+        match self.kind().data_length(){
+            Some(length) if length != self.data.len() => {
+                Err(AddressParseError::InvalidLength)
+            },
+            _ => {
+                Ok(())
+            }
+        }
+    }
+	/// Converts a full address into a short address.
+    /// Addresses of kinds which always have SHORT_ADDRESS_SIZE bytes of data keep their data unchanged, so that contract and pubkeyhash
+    /// addresses are identical as short and full addresses. The data of any other kind is replaced by the first 20 bytes of its SHA256 hash,
+    /// even if it is already 20 bytes long. Returns InvalidLength if the address does not have the amount of data required by its kind
+    pub fn to_short_address(&self) -> Result<NeutronShortAddress, AddressParseError>{
+        self.validate()?;
+        let mut data: [u8; SHORT_ADDRESS_SIZE] = Default::default();
+        if self.kind().data_length() == Some(SHORT_ADDRESS_SIZE){
+            data.copy_from_slice(&self.data);
+        }else{
+            let d = sha256(&self.data[0..]);
+            data.copy_from_slice(&d[0..SHORT_ADDRESS_SIZE]);
         }
+        Ok(NeutronShortAddress{
+            version: self.version,
+            data: data
+        })
 	}
+    /// Converts a short address back into its full address. This is only possible for kinds whose full addresses always have
+    /// exactly SHORT_ADDRESS_SIZE bytes of data. For any other kind the short address is a hash, so None is returned
+    pub fn from_short_address(address: &NeutronShortAddress) -> Option<NeutronAddress>{
+        if AddressKind::from_version(address.version).data_length() == Some(SHORT_ADDRESS_SIZE){
+            Some(NeutronAddress{
+                version: address.version,
+                data: address.data.to_vec()
+            })
+        }else{
+            None
+        }
+    }
 	
-	pub fn set_to_random_address(&mut self) {
-		self.data = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+	/// Replaces the address data with 32 random bytes. Tests should use the seeded random number generator of the Testbench so that they are reproducible
+	pub fn set_to_random_address<R: Rng>(&mut self, rng: &mut R) {
+		self.data = rng.gen::<[u8; 32]>().to_vec();
     }
-    pub fn new_random_address() -> NeutronAddress{
+    pub fn new_random_address<R: Rng>(rng: &mut R) -> NeutronAddress{
         let mut a = NeutronAddress::default();
-        a.set_to_random_address();
+        a.set_to_random_address(rng);
         a
     }
-}
\ No newline at end of file
+    /// Derives the address of a contract deployed by the given creator, using the creator's count of previous deployments as the nonce.
+    /// The address is the first 20 bytes of SHA256(0x00 || creator version || creator data || nonce), with integers encoded as little endian
+    pub fn contract_address(creator: &NeutronAddress, nonce: u64) -> NeutronAddress{
+        let mut preimage = vec![0];
+        preimage.extend_from_slice(&creator.version.to_le_bytes());
+        preimage.extend_from_slice(&creator.data);
+        preimage.extend_from_slice(&nonce.to_le_bytes());
+        NeutronAddress::contract_address_from_preimage(&preimage)
+    }
+    /// Derives the address of a contract deployed by the given creator using a salt, so that the address is known before deployment
+    /// regardless of the creator's nonce. The code hash is the SHA256 hash of the contract's code section.
+    /// The address is the first 20 bytes of SHA256(0x01 || creator version || creator data || salt || code hash)
+    pub fn contract_address_from_salt(creator: &NeutronAddress, salt: &[u8; 32], code_hash: &[u8; 32]) -> NeutronAddress{
+        let mut preimage = vec![1];
+        preimage.extend_from_slice(&creator.version.to_le_bytes());
+        preimage.extend_from_slice(&creator.data);
+        preimage.extend_from_slice(salt);
+        preimage.extend_from_slice(code_hash);
+        NeutronAddress::contract_address_from_preimage(&preimage)
+    }
+    fn contract_address_from_preimage(preimage: &[u8]) -> NeutronAddress{
+        //contracts are currently always x86
+        NeutronAddress{
+            version: AddressKind::X86Contract.version(),
+            data: sha256(preimage)[0..20].to_vec()
+        }
+    }
+}
+
+impl fmt::Display for NeutronAddress{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", to_bech32(&format!("n{}", self.version), &self.data))
+    }
+}
+
+impl FromStr for NeutronAddress{
+    type Err = AddressParseError;
+    fn from_str(s: &str) -> Result<NeutronAddress, AddressParseError>{
+        let (hrp, data) = from_bech32(s)?;
+        let address = NeutronAddress{
+            version: parse_prefixed_version(&hrp, "n")?,
+            data: data
+        };
+        address.validate()?;
+        Ok(address)
+    }
+}
+
+/// A short address in its text format, which is displayed and parsed using bech32.
+/// NeutronShortAddress is defined outside of this crate so can not implement Display and FromStr itself
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ShortAddressText(pub NeutronShortAddress);
+
+impl fmt::Display for ShortAddressText{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}", to_bech32(&format!("ns{}", self.0.version), &self.0.data))
+    }
+}
+
+impl FromStr for ShortAddressText{
+    type Err = AddressParseError;
+    fn from_str(s: &str) -> Result<ShortAddressText, AddressParseError>{
+        let (hrp, data) = from_bech32(s)?;
+        let mut address = NeutronShortAddress::default();
+        address.version = parse_prefixed_version(&hrp, "ns")?;
+        if data.len() != address.data.len(){
+            return Err(AddressParseError::InvalidLength);
+        }
+        address.data.copy_from_slice(&data);
+        Ok(ShortAddressText(address))
+    }
+}
+
+pub fn short_address_to_string(address: &NeutronShortAddress) -> String{
+    ShortAddressText(*address).to_string()
+}
+
+pub fn parse_short_address(s: &str) -> Result<NeutronShortAddress, AddressParseError>{
+    Ok(s.parse::<ShortAddressText>()?.0)
+}
+
+/// Encodes a short address using base58check, with a prefix byte giving its kind and network. See base58check_prefix
+pub fn short_address_to_base58check(address: &NeutronShortAddress, network: Network) -> Result<String, AddressParseError>{
+    let prefix = match base58check_prefix(AddressKind::from_version(address.version), network){
+        None => {
+            return Err(AddressParseError::UnsupportedVersion(address.version));
+        },
+        Some(p) => {
+            p
+        }
+    };
+    let mut payload = vec![prefix];
+    payload.extend_from_slice(&address.data);
+    Ok(to_base58check(&payload))
+}
+
+/// Parses a base58check address of the given network, taking its version from the kind given by its prefix byte
+pub fn short_address_from_base58check(s: &str, network: Network) -> Result<NeutronShortAddress, AddressParseError>{
+    let payload = from_base58check(s)?;
+    let kinds = [AddressKind::PubKeyHash, AddressKind::X86Contract, AddressKind::ScriptHash];
+    let kind = match kinds.iter().find(|k| payload.first() == base58check_prefix(**k, network).as_ref()){
+        None => {
+            return Err(AddressParseError::InvalidPrefix(format!("{:?}", payload.first())));
+        },
+        Some(k) => {
+            k
+        }
+    };
+    let mut address = NeutronShortAddress::default();
+    if payload.len() - 1 != address.data.len(){
+        return Err(AddressParseError::InvalidLength);
+    }
+    address.version = kind.version();
+    address.data.copy_from_slice(&payload[1..]);
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    #[test]
+    fn test_full_address_text(){
+        let address = NeutronAddress{
+            version: 100,
+            data: vec![1, 2, 3, 4, 5]
+        };
+        let text = address.to_string();
+        assert!(text.starts_with("n1001"));
+        assert_eq!(text.parse::<NeutronAddress>().unwrap(), address);
+        assert_eq!(text.to_uppercase().parse::<NeutronAddress>().unwrap(), address);
+        let random = NeutronAddress::new_random_address(&mut StdRng::seed_from_u64(0));
+        assert_eq!(random.to_string().parse::<NeutronAddress>().unwrap(), random);
+
+        let mut corrupted = text.clone().into_bytes();
+        let last = corrupted.len() - 1;
+        corrupted[last] = if corrupted[last] == b'q' { b'p' } else { b'q' };
+        assert_eq!(String::from_utf8(corrupted).unwrap().parse::<NeutronAddress>(), Err(AddressParseError::Encoding(DecodeError::InvalidChecksum)));
+        assert_eq!(to_bech32("n02", &[1]).parse::<NeutronAddress>(), Err(AddressParseError::InvalidPrefix("n02".to_string())));
+        assert_eq!(to_bech32("x2", &[1]).parse::<NeutronAddress>(), Err(AddressParseError::InvalidPrefix("x2".to_string())));
+        //known kinds of addresses must have the right amount of data
+        assert_eq!(to_bech32("n2", &[1; 19]).parse::<NeutronAddress>(), Err(AddressParseError::InvalidLength));
+        assert_eq!(to_bech32("n1", &[1; 20]).parse::<NeutronAddress>().unwrap().kind(), AddressKind::PubKeyHash);
+        //short addresses are not accepted as full addresses
+        let short = short_address_to_string(&NeutronShortAddress::default());
+        assert!(short.parse::<NeutronAddress>().is_err());
+    }
+    #[test]
+    fn test_short_address_text(){
+        let mut address = NeutronShortAddress::default();
+        address.version = 100;
+        address.data[5] = 20;
+        let text = short_address_to_string(&address);
+        assert!(text.starts_with("ns1001"));
+        assert_eq!(parse_short_address(&text).unwrap(), address);
+        assert_eq!(ShortAddressText(address).to_string(), text);
+        assert_eq!(text.parse::<ShortAddressText>(), Ok(ShortAddressText(address)));
+        assert_eq!(parse_short_address(&to_bech32("ns100", &[1; 19])), Err(AddressParseError::InvalidLength));
+        let full = NeutronAddress{
+            version: 100,
+            data: vec![0; 20]
+        };
+        assert!(parse_short_address(&full.to_string()).is_err());
+    }
+    #[test]
+    fn test_base58check_address(){
+        let mut address = NeutronShortAddress::default();
+        address.version = 1;
+        address.data[0] = 0xAB;
+        let mainnet = short_address_to_base58check(&address, Network::Mainnet).unwrap();
+        assert!(mainnet.starts_with('Q'));
+        assert_eq!(short_address_from_base58check(&mainnet, Network::Mainnet).unwrap(), address);
+        let testnet = short_address_to_base58check(&address, Network::Testnet).unwrap();
+        assert!(testnet.starts_with('q'));
+        assert_eq!(short_address_from_base58check(&testnet, Network::Testnet).unwrap(), address);
+        assert!(short_address_from_base58check(&testnet, Network::Mainnet).is_err());
+        //the version is given by the prefix
+        let expected = [(2, 'N', 'n'), (3, 'M', 'm')];
+        for (version, main, test) in expected.iter(){
+            address.version = *version;
+            let mainnet = short_address_to_base58check(&address, Network::Mainnet).unwrap();
+            assert!(mainnet.starts_with(*main));
+            assert_eq!(short_address_from_base58check(&mainnet, Network::Mainnet).unwrap(), address);
+            let testnet = short_address_to_base58check(&address, Network::Testnet).unwrap();
+            assert!(testnet.starts_with(*test));
+            assert_eq!(short_address_from_base58check(&testnet, Network::Testnet).unwrap(), address);
+        }
+        address.version = 100;
+        assert_eq!(short_address_to_base58check(&address, Network::Mainnet), Err(AddressParseError::UnsupportedVersion(100)));
+        assert_eq!(short_address_from_base58check(&to_base58check(&[0; 21]), Network::Mainnet), Err(AddressParseError::InvalidPrefix("Some(0)".to_string())));
+        assert_eq!(short_address_from_base58check(&to_base58check(&[QTUM_MAINNET_PUBKEYHASH_PREFIX; 20]), Network::Mainnet), Err(AddressParseError::InvalidLength));
+    }
+    #[test]
+    fn test_contract_address(){
+        let creator = NeutronAddress{
+            version: 1,
+            data: vec![7; 20]
+        };
+        let first = NeutronAddress::contract_address(&creator, 0);
+        assert_eq!(first.version, 2);
+        assert_eq!(first.data, from_hex("645f63740f06b533098e5dcaa6e73b58ee8550e3").unwrap());
+        assert_eq!(NeutronAddress::contract_address(&creator, 0), first);
+        assert!(NeutronAddress::contract_address(&creator, 1) != first);
+        let other = NeutronAddress{
+            version: 2,
+            data: vec![7; 20]
+        };
+        assert!(NeutronAddress::contract_address(&other, 0) != first);
+
+        let salted = NeutronAddress::contract_address_from_salt(&creator, &[3; 32], &[4; 32]);
+        assert_eq!(salted.data, from_hex("bd6ee81930cd970f00d2dc3d8ec4236db0de9d8d").unwrap());
+        assert!(NeutronAddress::contract_address_from_salt(&creator, &[3; 32], &[5; 32]) != salted);
+        assert!(NeutronAddress::contract_address_from_salt(&creator, &[6; 32], &[4; 32]) != salted);
+    }
+    #[test]
+    fn test_short_address_conversion(){
+        //pubkeyhash addresses
+        let pubkeyhash = NeutronAddress{
+            version: 1,
+            data: vec![3; 20]
+        };
+        let short = pubkeyhash.to_short_address().unwrap();
+        assert_eq!(short.version, 1);
+        assert_eq!(short.data, [3; 20]);
+        assert_eq!(NeutronAddress::from_short_address(&short), Some(pubkeyhash));
+        //x86 contract addresses
+        let contract = NeutronAddress::contract_address(&NeutronAddress::new_random_address(&mut StdRng::seed_from_u64(0)), 0);
+        let short = contract.to_short_address().unwrap();
+        assert_eq!(short.version, 2);
+        assert_eq!(&short.data[..], &contract.data[..]);
+        assert_eq!(NeutronAddress::from_short_address(&short), Some(contract));
+        //other versions are always hashed, whatever their amount of data
+        let other = NeutronAddress{
+            version: 100,
+            data: vec![1, 2, 3]
+        };
+        let short = other.to_short_address().unwrap();
+        assert_eq!(short.version, 100);
+        assert_eq!(&short.data[..], &sha256(&[1, 2, 3])[0..20]);
+        assert_eq!(NeutronAddress::from_short_address(&short), None);
+        let other = NeutronAddress{
+            version: 100,
+            data: vec![4; 20]
+        };
+        assert_eq!(&other.to_short_address().unwrap().data[..], &sha256(&[4; 20])[0..20]);
+        assert_eq!(NeutronAddress::from_short_address(&other.to_short_address().unwrap()), None);
+        //fixed length kinds with the wrong amount of data have no short address
+        let long = NeutronAddress{
+            version: 2,
+            data: vec![5; 32]
+        };
+        assert_eq!(long.to_short_address(), Err(AddressParseError::InvalidLength));
+        let truncated = NeutronAddress{
+            version: 1,
+            data: vec![5; 19]
+        };
+        assert_eq!(truncated.to_short_address(), Err(AddressParseError::InvalidLength));
+        //a short address only converts back to the address it was created from
+        let addresses = vec![NeutronAddress{version: 1, data: vec![6; 20]}, NeutronAddress{version: 100, data: vec![7; 32]}, other, long, truncated];
+        for address in addresses.iter(){
+            if let Ok(short) = address.to_short_address(){
+                if let Some(full) = NeutronAddress::from_short_address(&short){
+                    assert_eq!(&full, address);
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_address_kind(){
+        for version in 0..10{
+            assert_eq!(AddressKind::from_version(version).version(), version);
+        }
+        assert_eq!(AddressKind::from_version(1), AddressKind::PubKeyHash);
+        assert_eq!(AddressKind::from_version(2), AddressKind::X86Contract);
+        assert_eq!(AddressKind::from_version(3), AddressKind::ScriptHash);
+        assert_eq!(AddressKind::from_version(4), AddressKind::Other(4));
+        assert!(AddressKind::X86Contract.is_contract());
+        assert!(!AddressKind::PubKeyHash.is_contract());
+
+        let contract = NeutronAddress::new(AddressKind::X86Contract, vec![1; 20]).unwrap();
+        assert_eq!(contract.version, 2);
+        assert_eq!(contract.kind(), AddressKind::X86Contract);
+        assert_eq!(NeutronAddress::new(AddressKind::PubKeyHash, vec![1; 32]), Err(AddressParseError::InvalidLength));
+        assert_eq!(NeutronAddress::new(AddressKind::ScriptHash, vec![]), Err(AddressParseError::InvalidLength));
+        assert!(NeutronAddress::new(AddressKind::Other(10), vec![1; 32]).is_ok());
+        let script = NeutronAddress::new(AddressKind::ScriptHash, vec![2; 20]).unwrap();
+        assert_eq!(NeutronAddress::from_short_address(&script.to_short_address().unwrap()), Some(script));
+    }
+}
@@ -10,7 +10,9 @@ use crate::neutronerror::NeutronError::*;
 #[derive(Default)]
 pub struct ContractCallStack{
     data_stack: Vec<Vec<u8>>,
-    context_stack: Vec<ExecutionContext>
+    context_stack: Vec<ExecutionContext>,
+    /// Gas charged by the CallSystem which has not yet been deducted by the VM
+    pending_gas: u64
 }
 
 impl ContractCallStack{
@@ -74,6 +76,47 @@ impl ContractCallStack{
         Ok(self.data_stack.len() as u32)
     }
 
+    /// Pushes a u32 onto the SCCS as a 4 byte little endian item
+    pub fn push_sccs_u32(&mut self, value: u32) -> Result<(), NeutronError>{
+        self.push_sccs(&value.to_le_bytes())
+    }
+    /// Pushes a u64 onto the SCCS as an 8 byte little endian item
+    pub fn push_sccs_u64(&mut self, value: u64) -> Result<(), NeutronError>{
+        self.push_sccs(&value.to_le_bytes())
+    }
+    /// Pushes a full address onto the SCCS as a little endian u32 version followed by the address data
+    pub fn push_sccs_address(&mut self, address: &NeutronAddress) -> Result<(), NeutronError>{
+        let mut data = address.version.to_le_bytes().to_vec();
+        data.extend_from_slice(&address.data);
+        self.push_sccs(&data)
+    }
+    /// Pops a 4 byte little endian u32 off of the SCCS
+    pub fn pop_sccs_u32(&mut self) -> Result<u32, NeutronError>{
+        let item = self.pop_sccs()?;
+        if item.len() < 4{
+            return Err(Recoverable(RecoverableError::StackItemTooSmall));
+        }
+        if item.len() > 4{
+            return Err(Recoverable(RecoverableError::StackItemTooLarge));
+        }
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(&item);
+        Ok(u32::from_le_bytes(bytes))
+    }
+    /// Pops an 8 byte little endian u64 off of the SCCS
+    pub fn pop_sccs_u64(&mut self) -> Result<u64, NeutronError>{
+        let item = self.pop_sccs()?;
+        if item.len() < 8{
+            return Err(Recoverable(RecoverableError::StackItemTooSmall));
+        }
+        if item.len() > 8{
+            return Err(Recoverable(RecoverableError::StackItemTooLarge));
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&item);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
     /// Get total memory occupied by the SCCS
     /*
     pub fn sccs_memory_amount(&self) -> Result<u32, NeutronError>{
@@ -81,6 +124,17 @@ impl ContractCallStack{
     }
     */
 
+    /// Charges a gas cost to the current execution. The VM is responsible for deducting it after the current system call
+    pub fn charge_gas(&mut self, gas: u64){
+        self.pending_gas = self.pending_gas.saturating_add(gas);
+    }
+    /// Returns the gas charged since the last call to this function, resetting it to 0
+    pub fn take_pending_gas(&mut self) -> u64{
+        let gas = self.pending_gas;
+        self.pending_gas = 0;
+        gas
+    }
+
     /// Pushes a new execution context into the stack
     pub fn push_context(&mut self, context: ExecutionContext) -> Result<(), NeutronError>{
         self.context_stack.push(context);
@@ -136,8 +190,12 @@ impl ContractCallStack{
         self.push_context(c).unwrap();
     }
     /// Creates a top level context for deploying a new contract. The context stack MUST be empty
-    pub fn create_top_level_deploy(&mut self, address: NeutronAddress, sender: NeutronAddress, gas_limit: u64, value: u64){
+    /// Returns InvalidAddress if the address is not of a contract kind
+    pub fn create_top_level_deploy(&mut self, address: NeutronAddress, sender: NeutronAddress, gas_limit: u64, value: u64) -> Result<(), NeutronError>{
         assert!(self.context_stack.len() == 0);
+        if !address.kind().is_contract(){
+            return Err(Recoverable(RecoverableError::InvalidAddress));
+        }
         //todo: dedupicate
         let mut c = ExecutionContext::default();
         c.self_address = address.clone();
@@ -146,7 +204,7 @@ impl ContractCallStack{
         c.sender = sender.clone();
         c.origin = sender.clone();
         c.execution_type = ExecutionType::Deploy;
-        self.push_context(c).unwrap();
+        self.push_context(c)
     }
     /// Creates a new nested context for calling an existing contract. The context stack MUST NOT be empty
     pub fn create_call(&mut self, address: NeutronAddress, gas_limit: u64, value: u64){
@@ -161,8 +219,12 @@ impl ContractCallStack{
         self.push_context(c).unwrap();
     }
     /// Creates a new nested context for deploying a contract. The context stack MUST NOT be empty
-    pub fn create_deploy(&mut self, address: NeutronAddress, gas_limit: u64, value: u64){
+    /// Returns InvalidAddress if the address is not of a contract kind
+    pub fn create_deploy(&mut self, address: NeutronAddress, gas_limit: u64, value: u64) -> Result<(), NeutronError>{
         assert!(self.context_stack.len() > 0);
+        if !address.kind().is_contract(){
+            return Err(Recoverable(RecoverableError::InvalidAddress));
+        }
         let mut c = ExecutionContext::default();
         c.self_address = address.clone();
         c.gas_limit = gas_limit;
@@ -170,7 +232,7 @@ impl ContractCallStack{
         c.sender = self.peek_context(0).unwrap().self_address.clone();
         c.origin = self.context_stack.get(0).unwrap().sender.clone();
         c.execution_type = ExecutionType::Deploy;
-        self.push_context(c).unwrap();
+        self.push_context(c)
     }
 
 
@@ -0,0 +1,111 @@
+extern crate ring;
+extern crate ripemd;
+extern crate secp256k1;
+use ring::digest::{Context, SHA256};
+use ripemd::{Ripemd160, Digest};
+use secp256k1::{Secp256k1, Message, PublicKey, Signature};
+use secp256k1::recovery::{RecoverableSignature, RecoveryId};
+
+/// Computes the SHA256 hash of the given data
+pub fn sha256(data: &[u8]) -> [u8; 32]{
+    let mut context = Context::new(&SHA256);
+    context.update(data);
+    let d = context.finish();
+    let mut hash: [u8; 32] = Default::default();
+    hash.copy_from_slice(d.as_ref());
+    hash
+}
+
+/// Computes SHA256(SHA256(data)), as used by Bitcoin style blockchains for transaction and block hashes
+pub fn double_sha256(data: &[u8]) -> [u8; 32]{
+    sha256(&sha256(data))
+}
+
+/// Computes the RIPEMD160 hash of the given data
+pub fn ripemd160(data: &[u8]) -> [u8; 20]{
+    let mut hash: [u8; 20] = Default::default();
+    hash.copy_from_slice(&Ripemd160::digest(data));
+    hash
+}
+
+/// Verifies a 64 byte compact secp256k1 signature of a 32 byte message hash against a serialized public key
+/// Returns false for any malformed input as well as for signatures which do not match.
+/// Signatures with a high S value are accepted, as they are equally valid and not every signer normalizes them
+pub fn secp256k1_verify(hash: &[u8], signature: &[u8], public_key: &[u8]) -> bool{
+    let message = match Message::from_slice(hash){
+        Ok(m) => m,
+        Err(_) => return false
+    };
+    let mut signature = match Signature::from_compact(signature){
+        Ok(s) => s,
+        Err(_) => return false
+    };
+    //libsecp256k1 only verifies low S signatures
+    signature.normalize_s();
+    let public_key = match PublicKey::from_slice(public_key){
+        Ok(k) => k,
+        Err(_) => return false
+    };
+    Secp256k1::verification_only().verify(&message, &signature, &public_key).is_ok()
+}
+
+/// Recovers the compressed public key which created a 65 byte recoverable secp256k1 signature of a 32 byte message hash
+/// The signature is expected as the 64 byte compact signature followed by a single byte recovery ID
+pub fn secp256k1_recover(hash: &[u8], signature: &[u8]) -> Option<[u8; 33]>{
+    if signature.len() != 65{
+        return None;
+    }
+    let message = Message::from_slice(hash).ok()?;
+    let recovery_id = RecoveryId::from_i32(signature[64] as i32).ok()?;
+    let signature = RecoverableSignature::from_compact(&signature[0..64], recovery_id).ok()?;
+    let public_key = Secp256k1::verification_only().recover(&message, &signature).ok()?;
+    Some(public_key.serialize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secp256k1::SecretKey;
+    #[test]
+    fn test_hashes(){
+        assert_eq!(sha256(b"abc")[0..4], [0xba, 0x78, 0x16, 0xbf]);
+        assert_eq!(ripemd160(b"abc")[0..4], [0x8e, 0xb2, 0x08, 0xf7]);
+        assert_eq!(double_sha256(b"abc"), sha256(&sha256(b"abc")));
+    }
+    #[test]
+    fn test_secp256k1(){
+        let secp = Secp256k1::new();
+        let secret = SecretKey::from_slice(&[7; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(&secp, &secret).serialize();
+        let hash = sha256(b"neutron");
+        let message = Message::from_slice(&hash).unwrap();
+
+        let signature = secp.sign(&message, &secret).serialize_compact();
+        assert!(secp256k1_verify(&hash, &signature, &public_key));
+        assert!(!secp256k1_verify(&sha256(b"other"), &signature, &public_key));
+        assert!(!secp256k1_verify(&hash, &signature[1..], &public_key));
+
+        //the same signature with S replaced by the curve order minus S
+        const ORDER: [u8; 32] = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFE,
+            0xBA, 0xAE, 0xDC, 0xE6, 0xAF, 0x48, 0xA0, 0x3B, 0xBF, 0xD2, 0x5E, 0x8C, 0xD0, 0x36, 0x41, 0x41];
+        let mut high_s = signature;
+        let mut borrow = 0i16;
+        for i in (0..32).rev(){
+            let mut d = ORDER[i] as i16 - signature[32 + i] as i16 - borrow;
+            borrow = if d < 0 { 1 } else { 0 };
+            if d < 0{
+                d += 256;
+            }
+            high_s[32 + i] = d as u8;
+        }
+        assert!(high_s[32] >= 0x7F);
+        assert!(secp256k1_verify(&hash, &high_s, &public_key));
+        assert!(!secp256k1_verify(&sha256(b"other"), &high_s, &public_key));
+
+        let (recovery_id, compact) = secp.sign_recoverable(&message, &secret).serialize_compact();
+        let mut recoverable = compact.to_vec();
+        recoverable.push(recovery_id.to_i32() as u8);
+        assert_eq!(secp256k1_recover(&hash, &recoverable), Some(public_key));
+        assert_eq!(secp256k1_recover(&hash, &compact), None);
+    }
+}
@@ -1,9 +1,14 @@
 extern crate neutron_star_constants;
 use std::collections::HashMap;
+use std::collections::BTreeMap;
 //use std::collections::HashSet;
 use neutron_star_constants::*;
+use crate::merkle::*;
+use crate::snapshot::*;
 
 pub const NEUTRONDB_USER_SPACE: u8 = '_' as u8;
+/// The storage space holding information about an address which is managed by the host, such as its contract nonce
+pub const NEUTRONDB_ACCOUNT_SPACE: u8 = 1;
 
 #[derive(Debug)]
 pub enum NeutronDBError{
@@ -24,109 +29,474 @@ pub trait NeutronDB{
     /// Commits all state to the database 
     /// TBD: should this be left as a non-trait function??
     fn commit(&mut self) -> Result<(), NeutronDBError>;
-    //fn compute_new_proofs(&mut self, )
-    // Automatically will execute `collapse_checkpoints`. Returns the keys and values which were read in this context as well as the keys which were written to
-    //fn compute_state_differences(&mut self, reads: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>, writes: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>)
-    //    -> Result<(), NeutronDBError>;
+    /// Gets the block height up to which rent has been paid for a key, or None if the key has no rent bookkeeping
+    fn read_rent(&mut self, address: &NeutronShortAddress, key: &[u8]) -> Result<Option<u32>, NeutronDBError>;
+    /// Sets the block height up to which rent has been paid for a key. Follows the same checkpoint semantics as write_key
+    fn write_rent(&mut self, address: &NeutronShortAddress, key: &[u8], paid_until: u32) -> Result<(), NeutronDBError>;
+    /// The state root committing to all (address, key, value) triples as of the last commit. See the merkle module for the format
+    fn state_root(&self) -> [u8; 32];
+    /// Generates a proof of the value, or the absence, of a key in the committed state against state_root
+    fn prove_key(&self, address: &NeutronShortAddress, key: &[u8]) -> Result<StateProof, NeutronDBError>;
+    /// Lists the keys of an address which start with the given prefix along with their current values, ordered by key.
+    /// Uncommitted state is included. An empty prefix lists every key, while a single byte prefix such as NEUTRONDB_USER_SPACE lists a whole space
+    fn scan_keys(&self, address: &NeutronShortAddress, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, NeutronDBError>;
+    /// Starts keeping every committed version of each key so that state can be read as of past block heights.
+    /// History begins with the state as of the current block height. If history is already kept, only the pruning policy is changed
+    fn enable_history(&mut self, pruning: HistoryPruning);
+    /// Sets the block height which following commits are tagged with
+    fn set_block_height(&mut self, height: u32);
+    /// Reads a key as of the last commit made at or before the given block height. Returns None if the key did not exist at that height.
+    /// Returns an error if history is not kept or if the height is earlier than the history which has been kept
+    fn read_key_at(&self, address: &NeutronShortAddress, key: &[u8], height: u32) -> Result<Option<Vec<u8>>, NeutronDBError>;
+    /// The earliest block height which can be read using read_key_at, or None if history is not kept
+    fn earliest_history_height(&self) -> Option<u32>;
+    /// Copies out all committed state, for saving and later restoring using DBSnapshot::restore
+    fn snapshot(&self) -> DBSnapshot;
+    /// The amount of state held by an address, including uncommitted state
+    fn storage_usage(&self, address: &NeutronShortAddress) -> StorageUsage;
+    /// Automatically will execute `collapse_checkpoints`. Returns the keys and values which were read in this context as well as the keys which were written to
+    /// Reads and writes made within reverted checkpoints are not included
+    fn compute_state_differences(&mut self) -> Result<StateDifferences, NeutronDBError>;
 }
+
+/// The read and write sets of the state accessed since the last commit
+#[derive(Default, Debug, Clone, Eq, PartialEq)]
+pub struct StateDifferences{
+    /// Keys which were read from committed storage along with the value read, or None if the key did not exist.
+    /// Keys which were only read after being written within the same context are not included
+    pub reads: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Option<Vec<u8>>>>,
+    /// The final values of all keys which were written
+    pub writes: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>
+}
+/// The amount of state held by a single address across all storage spaces
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct StorageUsage{
+    /// The number of keys
+    pub keys: u64,
+    /// The total size of all keys and values
+    pub bytes: u64
+}
+
+impl StorageUsage{
+    fn add(&mut self, key_size: usize, value_size: usize){
+        self.keys += 1;
+        self.bytes += (key_size + value_size) as u64;
+    }
+    /// Fails without modifying the usage if it does not include the key, meaning the usage is out of sync with storage
+    fn remove(&mut self, key_size: usize, value_size: usize) -> Result<(), NeutronDBError>{
+        match (self.keys.checked_sub(1), self.bytes.checked_sub((key_size + value_size) as u64)){
+            (Some(keys), Some(bytes)) => {
+                self.keys = keys;
+                self.bytes = bytes;
+                Ok(())
+            },
+            _ => {
+                Err(NeutronDBError::Unrecoverable)
+            }
+        }
+    }
+}
+
+/// How much state history a database with history enabled keeps
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum HistoryPruning{
+    /// Every version of every key is kept
+    KeepAll,
+    /// Only state as of the given number of blocks before the latest commit, or later, can be read
+    KeepBlocks(u32)
+}
+
+/// Every committed version of each key, tagged with the block height it was committed at
+#[derive(Debug, Clone)]
+struct StateHistory{
+    pruning: HistoryPruning,
+    /// The earliest block height which can be read
+    earliest: u32,
+    /// The versions of each key as (height, value), ordered by height
+    versions: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<(u32, Vec<u8>)>>>
+}
+
+impl StateHistory{
+    fn record(&mut self, height: u32, address: &NeutronShortAddress, key: &[u8], value: &[u8]){
+        let versions = self.versions.entry(*address).or_insert_with(HashMap::new).entry(key.to_vec()).or_insert_with(Vec::new);
+        match versions.binary_search_by_key(&height, |(h, _)| *h){
+            Ok(i) => {
+                versions[i].1 = value.to_vec();
+            },
+            Err(i) => {
+                versions.insert(i, (height, value.to_vec()));
+            }
+        }
+    }
+    /// Discards versions which can no longer be read under the pruning policy, given the height of the latest commit
+    fn prune(&mut self, height: u32){
+        let oldest = match self.pruning{
+            HistoryPruning::KeepAll => {
+                return;
+            },
+            HistoryPruning::KeepBlocks(blocks) => {
+                height.saturating_sub(blocks)
+            }
+        };
+        if oldest <= self.earliest{
+            return;
+        }
+        self.earliest = oldest;
+        for kv in self.versions.values_mut(){
+            for versions in kv.values_mut(){
+                //keep the version which was current as of the oldest readable height
+                let current = versions.iter().take_while(|(h, _)| *h <= oldest).count();
+                if current > 1{
+                    versions.drain(..current - 1);
+                }
+            }
+        }
+    }
+    fn read(&self, address: &NeutronShortAddress, key: &[u8], height: u32) -> Result<Option<Vec<u8>>, NeutronDBError>{
+        if height < self.earliest{
+            return Err(NeutronDBError::Recoverable);
+        }
+        match self.versions.get(address).and_then(|kv| kv.get(key)){
+            None => {
+                Ok(None)
+            },
+            Some(versions) => {
+                Ok(versions.iter().rev().find(|(h, _)| *h <= height).map(|(_, v)| v.clone()))
+            }
+        }
+    }
+}
+
+/// The modifications made within a single checkpoint, recorded so that they can be undone
+#[derive(Default, Debug, Clone)]
+struct Checkpoint{
+    /// The previous uncommitted value of each key first written within this checkpoint, or None if it had not been written yet
+    values: HashMap<(NeutronShortAddress, Vec<u8>), Option<Vec<u8>>>,
+    /// The previous uncommitted rent of each key whose rent was first modified within this checkpoint
+    rents: HashMap<(NeutronShortAddress, Vec<u8>), Option<u32>>,
+    /// The keys which were first read from storage within this checkpoint
+    touched: Vec<(NeutronShortAddress, Vec<u8>)>
+}
+
+impl Checkpoint{
+    /// Merges a checkpoint made after this one into it. For each key the earliest undo value is kept
+    fn merge(&mut self, later: Checkpoint){
+        for (key, previous) in later.values{
+            self.values.entry(key).or_insert(previous);
+        }
+        for (key, previous) in later.rents{
+            self.rents.entry(key).or_insert(previous);
+        }
+        self.touched.extend(later.touched);
+    }
+}
+
+/// Removes a key from a nested map, removing the address's map as well once it is empty
+fn remove_nested<V>(map: &mut HashMap<NeutronShortAddress, HashMap<Vec<u8>, V>>, address: &NeutronShortAddress, key: &[u8]){
+    let empty = match map.get_mut(address){
+        Some(kv) => {
+            kv.remove(key);
+            kv.is_empty()
+        },
+        None => {
+            false
+        }
+    };
+    if empty{
+        map.remove(address);
+    }
+}
+
+fn merge_nested<V>(into: &mut HashMap<NeutronShortAddress, HashMap<Vec<u8>, V>>, from: HashMap<NeutronShortAddress, HashMap<Vec<u8>, V>>){
+    for (address, kv) in from{
+        let target = into.entry(address).or_insert_with(HashMap::new);
+        for (k, v) in kv{
+            target.insert(k, v);
+        }
+    }
+}
+
+/// A simple in-memory database. Uncommitted state is kept in a single map which is layered over committed storage,
+/// while each checkpoint keeps a journal of the values it overwrote so that reverting only touches the keys it modified
 #[derive(Default,  Debug, Clone)]
 pub struct ProtoDB{
     storage: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>,
+    /// The block height up to which rent is paid for each key
+    rents: HashMap<NeutronShortAddress, HashMap<Vec<u8>, u32>>,
+    /// All state written since the last commit which has not been reverted
+    pending: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>,
+    /// All rent modified since the last commit which has not been reverted
+    pending_rents: HashMap<NeutronShortAddress, HashMap<Vec<u8>, u32>>,
     /// This only tracks keys which are read from storage, and ignores checkpoint-only data and reverts
-    //touched: HashMap<NeutronShortAddress, Vec<u8>>,
-    //rents: HashMap<Vec<u8>, u32>,
-    checkpoints: Vec<HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>>
+    touched: HashMap<NeutronShortAddress, HashMap<Vec<u8>, Option<Vec<u8>>>>,
+    checkpoints: Vec<Checkpoint>,
+    /// Recomputed over the entirety of storage upon each commit
+    state_root: [u8; 32],
+    /// The block height which commits are tagged with
+    height: u32,
+    /// Only kept once enabled
+    history: Option<StateHistory>,
+    /// The storage usage of each address, including uncommitted state
+    usage: HashMap<NeutronShortAddress, StorageUsage>,
+    /// The storage usage of each address as of the last commit
+    committed_usage: HashMap<NeutronShortAddress, StorageUsage>
+}
+
+impl ProtoDB{
+    /// Collapses all checkpoints and returns the rent modifications which would be applied by the next commit
+    pub(crate) fn uncommitted_rents(&mut self) -> Result<HashMap<NeutronShortAddress, HashMap<Vec<u8>, u32>>, NeutronDBError>{
+        self.collapse_checkpoints()?;
+        Ok(self.pending_rents.clone())
+    }
+    /// The size of the current value of a key, including uncommitted state
+    fn value_size(&self, address: &NeutronShortAddress, key: &[u8]) -> Option<usize>{
+        match self.pending.get(address).and_then(|kv| kv.get(key)){
+            Some(v) => {
+                Some(v.len())
+            },
+            None => {
+                self.storage.get(address).and_then(|kv| kv.get(key)).map(|v| v.len())
+            }
+        }
+    }
+    /// Updates the storage usage of an address after the value of one of its keys changes size
+    fn update_usage(&mut self, address: &NeutronShortAddress, key: &[u8], old_size: Option<usize>, new_size: Option<usize>) -> Result<(), NeutronDBError>{
+        let mut usage = self.usage.get(address).cloned().unwrap_or_default();
+        if let Some(size) = old_size{
+            usage.remove(key.len(), size)?;
+        }
+        if let Some(size) = new_size{
+            usage.add(key.len(), size);
+        }
+        if usage.keys == 0{
+            self.usage.remove(address);
+        }else{
+            self.usage.insert(*address, usage);
+        }
+        Ok(())
+    }
 }
 impl NeutronDB for ProtoDB{
     fn read_key(&mut self, address: &NeutronShortAddress, key: &[u8]) -> Result<Vec<u8>, NeutronDBError>{
-        for checkpoint in self.checkpoints.iter().rev(){
-            match checkpoint.get(address){
-                Some(kv) => {
-                    match kv.get(key){
-                        Some(v) => {
-                            return Ok(v.to_vec());
-                        },
-                        None => {}
-                    }
-                },
-                None => {
+        if let Some(v) = self.pending.get(address).and_then(|kv| kv.get(key)){
+            return Ok(v.clone());
+        }
+        let value = self.storage.get(address).and_then(|kv| kv.get(key));
+        match self.checkpoints.last_mut(){
+            Some(c) => {
+                let touched = self.touched.entry(*address).or_insert_with(HashMap::new);
+                if !touched.contains_key(key){
+                    touched.insert(key.to_vec(), value.cloned());
+                    c.touched.push((*address, key.to_vec()));
                 }
+            },
+            None => {
             }
         }
-        match self.storage.get(address){
-            Some(kv) => {
-                match kv.get(key){
-                    Some(v) => {
-                        return Ok(v.to_vec());
-                    },
-                    None => {
-                    }
-                }
+        match value{
+            Some(v) => {
+                Ok(v.clone())
             },
             None => {
+                Err(NeutronDBError::Unrecoverable)
             }
         }
-        Err(NeutronDBError::Unrecoverable)
     }
     fn write_key(&mut self, address: &NeutronShortAddress, key: &[u8], value: &[u8]) -> Result<(), NeutronDBError>{
         if self.checkpoints.len() == 0{
             return Err(NeutronDBError::Recoverable);
         }
-        let c = self.checkpoints.last_mut().unwrap();
-        match c.get_mut(address){
-            Some(kv) => {
-                kv.insert(key.to_vec(), value.to_vec());
-            },
-            None => {
-                let mut t = HashMap::new();
-                t.insert(key.to_vec(), value.to_vec());
-                c.insert(*address, t);
-            }
-        }
+        let old_size = self.value_size(address, key);
+        self.update_usage(address, key, old_size, Some(value.len()))?;
+        let kv = self.pending.entry(*address).or_insert_with(HashMap::new);
+        let previous = kv.insert(key.to_vec(), value.to_vec());
+        self.checkpoints.last_mut().unwrap().values.entry((*address, key.to_vec())).or_insert(previous);
         Ok(())
     }
     fn checkpoint(&mut self) -> Result<u32, NeutronDBError>{
-        self.checkpoints.push(HashMap::new());
+        self.checkpoints.push(Checkpoint::default());
         Ok(self.checkpoints.len() as u32)
     }
     fn revert_checkpoint(&mut self) -> Result<u32, NeutronDBError>{
-        if self.checkpoints.pop().is_none(){
-            Err(NeutronDBError::Unrecoverable)
-        }else{
-            Ok(self.checkpoints.len() as u32)
+        let c = match self.checkpoints.pop(){
+            None => {
+                return Err(NeutronDBError::Unrecoverable);
+            },
+            Some(c) => {
+                c
+            }
+        };
+        //the revert is always completed, even if the storage usage turns out to be out of sync
+        let mut usage_result = Ok(());
+        for ((address, key), previous) in c.values{
+            let old_size = self.value_size(&address, &key);
+            match previous{
+                Some(v) => {
+                    self.pending.entry(address).or_insert_with(HashMap::new).insert(key.clone(), v);
+                },
+                None => {
+                    remove_nested(&mut self.pending, &address, &key);
+                }
+            }
+            let new_size = self.value_size(&address, &key);
+            if let Err(e) = self.update_usage(&address, &key, old_size, new_size){
+                usage_result = Err(e);
+            }
         }
+        for ((address, key), previous) in c.rents{
+            match previous{
+                Some(v) => {
+                    self.pending_rents.entry(address).or_insert_with(HashMap::new).insert(key, v);
+                },
+                None => {
+                    remove_nested(&mut self.pending_rents, &address, &key);
+                }
+            }
+        }
+        for (address, key) in c.touched{
+            remove_nested(&mut self.touched, &address, &key);
+        }
+        usage_result?;
+        Ok(self.checkpoints.len() as u32)
     }
     fn collapse_checkpoints(&mut self) -> Result<(), NeutronDBError>{
-        let mut collapsed = HashMap::new();
-        for kv in self.checkpoints.iter_mut(){
-            for (key, value) in kv.drain(){
-                collapsed.insert(key, value);
-            }
+        if self.checkpoints.len() <= 1{
+            return Ok(());
+        }
+        let mut layers = self.checkpoints.drain(..);
+        let mut collapsed = layers.next().unwrap();
+        for c in layers{
+            collapsed.merge(c);
         }
-        self.checkpoints.clear();
         self.checkpoints.push(collapsed);
-        
         Ok(())
     }
     fn commit(&mut self) -> Result<(), NeutronDBError>{
-        self.collapse_checkpoints()?;
-        for (key, value) in self.checkpoints.last_mut().unwrap().drain(){
-            match self.storage.get_mut(&key){
-                None => {
-                    self.storage.insert(key, value);
-                },
-                Some(kv) => {
-                    for(k2, v2) in value{
-                        kv.insert(k2, v2);
-                    }
+        if let Some(history) = self.history.as_mut(){
+            for (address, kv) in self.pending.iter(){
+                for (k, v) in kv.iter(){
+                    history.record(self.height, address, k, v);
                 }
             }
+            history.prune(self.height);
         }
+        merge_nested(&mut self.storage, std::mem::replace(&mut self.pending, HashMap::new()));
+        merge_nested(&mut self.rents, std::mem::replace(&mut self.pending_rents, HashMap::new()));
+        self.committed_usage = self.usage.clone();
         self.clear_checkpoints();
+        self.state_root = compute_state_root(&self.storage);
         Ok(())
     }
+    fn state_root(&self) -> [u8; 32]{
+        self.state_root
+    }
+    fn enable_history(&mut self, pruning: HistoryPruning){
+        if let Some(history) = self.history.as_mut(){
+            history.pruning = pruning;
+            return;
+        }
+        let mut history = StateHistory{
+            pruning: pruning,
+            earliest: self.height,
+            versions: HashMap::new()
+        };
+        for (address, kv) in self.storage.iter(){
+            for (k, v) in kv.iter(){
+                history.record(self.height, address, k, v);
+            }
+        }
+        self.history = Some(history);
+    }
+    fn set_block_height(&mut self, height: u32){
+        self.height = height;
+    }
+    fn read_key_at(&self, address: &NeutronShortAddress, key: &[u8], height: u32) -> Result<Option<Vec<u8>>, NeutronDBError>{
+        match self.history.as_ref(){
+            None => {
+                Err(NeutronDBError::Recoverable)
+            },
+            Some(history) => {
+                history.read(address, key, height)
+            }
+        }
+    }
+    fn earliest_history_height(&self) -> Option<u32>{
+        self.history.as_ref().map(|h| h.earliest)
+    }
+    fn storage_usage(&self, address: &NeutronShortAddress) -> StorageUsage{
+        self.usage.get(address).cloned().unwrap_or_default()
+    }
+    fn snapshot(&self) -> DBSnapshot{
+        let mut entries: BTreeMap<(u32, Vec<u8>, Vec<u8>), SnapshotEntry> = BTreeMap::new();
+        for (address, kv) in self.storage.iter(){
+            for (k, v) in kv.iter(){
+                let entry = entries.entry((address.version, address.data.to_vec(), k.clone())).or_insert_with(SnapshotEntry::default);
+                entry.value = Some(v.clone());
+            }
+        }
+        for (address, kv) in self.rents.iter(){
+            for (k, rent) in kv.iter(){
+                let entry = entries.entry((address.version, address.data.to_vec(), k.clone())).or_insert_with(SnapshotEntry::default);
+                entry.rent = Some(*rent);
+            }
+        }
+        DBSnapshot{
+            format_version: SNAPSHOT_FORMAT_VERSION,
+            height: self.height,
+            state_root: self.state_root,
+            entries: entries.into_iter().map(|((version, data, key), mut entry)| {
+                entry.address_version = version;
+                entry.address_data = data;
+                entry.key = key;
+                entry
+            }).collect()
+        }
+    }
+    fn compute_state_differences(&mut self) -> Result<StateDifferences, NeutronDBError>{
+        self.collapse_checkpoints()?;
+        Ok(StateDifferences{
+            reads: self.touched.clone(),
+            writes: self.pending.clone()
+        })
+    }
+    fn scan_keys(&self, address: &NeutronShortAddress, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, NeutronDBError>{
+        let mut keys = BTreeMap::new();
+        for layer in [&self.storage, &self.pending].iter(){
+            if let Some(kv) = layer.get(address){
+                for (k, v) in kv.iter().filter(|(k, _)| k.starts_with(prefix)){
+                    keys.insert(k.clone(), v.clone());
+                }
+            }
+        }
+        Ok(keys.into_iter().collect())
+    }
+    fn prove_key(&self, address: &NeutronShortAddress, key: &[u8]) -> Result<StateProof, NeutronDBError>{
+        Ok(generate_state_proof(&sorted_leaves(&self.storage), &state_leaf_key(address, key)))
+    }
     fn clear_checkpoints(&mut self){
         self.checkpoints.clear();
+        self.pending.clear();
+        self.pending_rents.clear();
+        self.touched.clear();
+        self.usage = self.committed_usage.clone();
+    }
+    fn read_rent(&mut self, address: &NeutronShortAddress, key: &[u8]) -> Result<Option<u32>, NeutronDBError>{
+        if let Some(v) = self.pending_rents.get(address).and_then(|kv| kv.get(key)){
+            return Ok(Some(*v));
+        }
+        Ok(self.rents.get(address).and_then(|kv| kv.get(key)).cloned())
+    }
+    fn write_rent(&mut self, address: &NeutronShortAddress, key: &[u8], paid_until: u32) -> Result<(), NeutronDBError>{
+        match self.checkpoints.last_mut(){
+            None => {
+                Err(NeutronDBError::Recoverable)
+            },
+            Some(c) => {
+                let previous = self.pending_rents.entry(*address).or_insert_with(HashMap::new).insert(key.to_vec(), paid_until);
+                c.rents.entry((*address, key.to_vec())).or_insert(previous);
+                Ok(())
+            }
+        }
     }
 }
 #[cfg(test)]
@@ -204,5 +574,441 @@ mod tests {
         assert!(v == vec![10]);
         
     }
-    
-}
\ No newline at end of file
+    #[test]
+    fn test_rent(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert!(db.write_rent(&a, &[1], 10).is_err());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_rent(&a, &[1], 10).is_ok());
+        assert!(db.commit().is_ok());
+        assert_eq!(db.read_rent(&a, &[1]).unwrap(), Some(10));
+        assert_eq!(db.read_rent(&a, &[2]).unwrap(), None);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_rent(&a, &[1], 20).is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_rent(&a, &[1], 30).is_ok());
+        assert!(db.revert_checkpoint().is_ok());
+        assert_eq!(db.read_rent(&a, &[1]).unwrap(), Some(20));
+        db.clear_checkpoints();
+        assert_eq!(db.read_rent(&a, &[1]).unwrap(), Some(10));
+    }
+    #[test]
+    fn test_state_root(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert_eq!(db.state_root(), EMPTY_STATE_ROOT);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8]).is_ok());
+        //uncommitted state does not affect the state root
+        assert_eq!(db.state_root(), EMPTY_STATE_ROOT);
+        assert!(db.commit().is_ok());
+        let root = db.state_root();
+        assert_ne!(root, EMPTY_STATE_ROOT);
+
+        let mut db2 = ProtoDB::default();
+        assert!(db2.checkpoint().is_ok());
+        assert!(db2.write_key(&a, &[1], &[7]).is_ok());
+        assert!(db2.commit().is_ok());
+        assert_ne!(db2.state_root(), root);
+        assert!(db2.checkpoint().is_ok());
+        assert!(db2.write_key(&a, &[1], &[8]).is_ok());
+        assert!(db2.commit().is_ok());
+        assert_eq!(db2.state_root(), root);
+    }
+    #[test]
+    fn test_prove_key(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8]).is_ok());
+        assert!(db.write_key(&a, &[3], &[9]).is_ok());
+        assert!(db.commit().is_ok());
+        let proof = db.prove_key(&a, &[3]).unwrap();
+        assert_eq!(verify_state_proof(&db.state_root(), &a, &[3], &proof), Ok(Some(vec![9])));
+        let proof = db.prove_key(&a, &[2]).unwrap();
+        assert_eq!(verify_state_proof(&db.state_root(), &a, &[2], &proof), Ok(None));
+    }
+    #[test]
+    fn test_state_differences(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8]).is_ok());
+        assert!(db.commit().is_ok());
+
+        assert!(db.checkpoint().is_ok());
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![8]);
+        assert!(db.read_key(&a, &[2]).is_err());
+        assert!(db.write_key(&a, &[3], &[9]).is_ok());
+        //reads of checkpoint-only data are not tracked
+        assert_eq!(db.read_key(&a, &[3]).unwrap(), vec![9]);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.read_key(&a, &[4]).is_err());
+        assert!(db.write_key(&a, &[5], &[10]).is_ok());
+        assert!(db.revert_checkpoint().is_ok());
+
+        let diff = db.compute_state_differences().unwrap();
+        let reads = diff.reads.get(&a).unwrap();
+        assert_eq!(reads.len(), 2);
+        assert_eq!(reads.get(&vec![1]), Some(&Some(vec![8])));
+        assert_eq!(reads.get(&vec![2]), Some(&None));
+        let writes = diff.writes.get(&a).unwrap();
+        assert_eq!(writes.len(), 1);
+        assert_eq!(writes.get(&vec![3]), Some(&vec![9]));
+
+        assert!(db.commit().is_ok());
+        assert_eq!(db.compute_state_differences().unwrap(), StateDifferences::default());
+    }
+    #[test]
+    fn test_collapse_merges_keys(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8]).is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[2], &[9]).is_ok());
+        assert!(db.collapse_checkpoints().is_ok());
+        assert!(db.commit().is_ok());
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![8]);
+        assert_eq!(db.read_key(&a, &[2]).unwrap(), vec![9]);
+    }
+    #[test]
+    fn test_scan_keys(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut b = a;
+        b.data[5] = 21;
+        let mut db = ProtoDB::default();
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1, 2], &[8]).is_ok());
+        assert!(db.write_key(&a, &[1, 1], &[7]).is_ok());
+        assert!(db.write_key(&a, &[2], &[6]).is_ok());
+        assert!(db.write_key(&b, &[1, 0], &[5]).is_ok());
+        assert!(db.commit().is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1, 2], &[9]).is_ok());
+        assert!(db.write_key(&a, &[1, 0], &[4]).is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1, 3], &[3]).is_ok());
+        assert!(db.revert_checkpoint().is_ok());
+        assert_eq!(db.scan_keys(&a, &[1]).unwrap(), vec![
+            (vec![1, 0], vec![4]),
+            (vec![1, 1], vec![7]),
+            (vec![1, 2], vec![9])
+        ]);
+        assert_eq!(db.scan_keys(&a, &[]).unwrap().len(), 4);
+        assert_eq!(db.scan_keys(&a, &[3]).unwrap(), vec![]);
+        db.clear_checkpoints();
+        assert_eq!(db.scan_keys(&a, &[1]).unwrap(), vec![
+            (vec![1, 1], vec![7]),
+            (vec![1, 2], vec![8])
+        ]);
+    }
+    #[test]
+    fn test_history(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert!(db.read_key_at(&a, &[1], 0).is_err());
+        assert_eq!(db.earliest_history_height(), None);
+        db.set_block_height(5);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[1]).is_ok());
+        assert!(db.commit().is_ok());
+        db.set_block_height(10);
+        db.enable_history(HistoryPruning::KeepAll);
+        for height in 11..15{
+            db.set_block_height(height);
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[height as u8]).is_ok());
+            assert!(db.commit().is_ok());
+        }
+        //commits within the same block replace each other
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[100]).is_ok());
+        assert!(db.write_key(&a, &[2], &[2]).is_ok());
+        assert!(db.commit().is_ok());
+        assert_eq!(db.earliest_history_height(), Some(10));
+        assert!(db.read_key_at(&a, &[1], 9).is_err());
+        assert_eq!(db.read_key_at(&a, &[1], 10).unwrap(), Some(vec![1]));
+        assert_eq!(db.read_key_at(&a, &[1], 12).unwrap(), Some(vec![12]));
+        assert_eq!(db.read_key_at(&a, &[1], 14).unwrap(), Some(vec![100]));
+        assert_eq!(db.read_key_at(&a, &[1], 1000).unwrap(), Some(vec![100]));
+        assert_eq!(db.read_key_at(&a, &[2], 13).unwrap(), None);
+        assert_eq!(db.read_key_at(&a, &[2], 14).unwrap(), Some(vec![2]));
+
+        db.enable_history(HistoryPruning::KeepBlocks(2));
+        db.set_block_height(20);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[3], &[3]).is_ok());
+        assert!(db.commit().is_ok());
+        assert_eq!(db.earliest_history_height(), Some(18));
+        assert!(db.read_key_at(&a, &[1], 17).is_err());
+        assert_eq!(db.read_key_at(&a, &[1], 18).unwrap(), Some(vec![100]));
+        assert_eq!(db.read_key_at(&a, &[3], 19).unwrap(), None);
+        assert_eq!(db.read_key_at(&a, &[3], 20).unwrap(), Some(vec![3]));
+    }
+    #[test]
+    fn test_storage_usage(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        assert_eq!(db.storage_usage(&a), StorageUsage::default());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8, 8]).is_ok());
+        assert!(db.write_key(&a, &[2, 2], &[9]).is_ok());
+        assert_eq!(db.storage_usage(&a), StorageUsage{keys: 2, bytes: 6});
+        assert!(db.commit().is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[]).is_ok());
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[3], &[1, 2, 3]).is_ok());
+        assert_eq!(db.storage_usage(&a), StorageUsage{keys: 3, bytes: 8});
+        assert!(db.revert_checkpoint().is_ok());
+        assert_eq!(db.storage_usage(&a), StorageUsage{keys: 2, bytes: 4});
+        db.clear_checkpoints();
+        assert_eq!(db.storage_usage(&a), StorageUsage{keys: 2, bytes: 6});
+        //removing a key which was never counted fails rather than wrapping around
+        let mut usage = StorageUsage{keys: 1, bytes: 2};
+        assert!(usage.remove(1, 1).is_ok());
+        assert!(usage.remove(1, 1).is_err());
+        assert_eq!(usage, StorageUsage::default());
+    }
+}
+
+/// Compares ProtoDB against a naive model which copies the entire state for each checkpoint
+#[cfg(test)]
+mod model_tests {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashSet;
+
+    #[derive(Clone, Debug)]
+    enum Op{
+        Checkpoint,
+        Revert,
+        Collapse,
+        Commit,
+        Clear,
+        Read(u8, u8),
+        Write(u8, u8, Vec<u8>),
+        ReadRent(u8, u8),
+        Scan(u8),
+        Usage(u8),
+        WriteRent(u8, u8, u32),
+        Differences
+    }
+
+    #[derive(Clone, Default)]
+    struct ModelState{
+        values: HashMap<(u8, u8), Vec<u8>>,
+        rents: HashMap<(u8, u8), u32>,
+        written: HashSet<(u8, u8)>,
+        touched: HashMap<(u8, u8), Option<Vec<u8>>>
+    }
+
+    #[derive(Default)]
+    struct Model{
+        committed: ModelState,
+        layers: Vec<ModelState>
+    }
+
+    impl Model{
+        fn current(&self) -> &ModelState{
+            self.layers.last().unwrap_or(&self.committed)
+        }
+    }
+
+    fn address(a: u8) -> NeutronShortAddress{
+        let mut address = NeutronShortAddress::default();
+        address.version = 2;
+        address.data[0] = a;
+        address
+    }
+
+    fn nest<V: Clone>(flat: &HashMap<(u8, u8), V>) -> HashMap<NeutronShortAddress, HashMap<Vec<u8>, V>>{
+        let mut nested = HashMap::new();
+        for ((a, k), v) in flat.iter(){
+            nested.entry(address(*a)).or_insert_with(HashMap::new).insert(vec![*k], v.clone());
+        }
+        nested
+    }
+
+    fn op_strategy() -> impl Strategy<Value = Op>{
+        prop_oneof![
+            3 => Just(Op::Checkpoint),
+            2 => Just(Op::Revert),
+            1 => Just(Op::Collapse),
+            1 => Just(Op::Commit),
+            1 => Just(Op::Clear),
+            4 => (0..3u8, 0..4u8).prop_map(|(a, k)| Op::Read(a, k)),
+            4 => (0..3u8, 0..4u8, proptest::collection::vec(any::<u8>(), 0..3)).prop_map(|(a, k, v)| Op::Write(a, k, v)),
+            1 => (0..3u8, 0..4u8).prop_map(|(a, k)| Op::ReadRent(a, k)),
+            1 => (0..3u8).prop_map(|a| Op::Scan(a)),
+            1 => (0..3u8).prop_map(|a| Op::Usage(a)),
+            1 => (0..3u8, 0..4u8, any::<u32>()).prop_map(|(a, k, r)| Op::WriteRent(a, k, r)),
+            1 => Just(Op::Differences)
+        ]
+    }
+
+    /// Applies the operations to both ProtoDB and the model, checking that every result matches
+    fn check_ops(ops: Vec<Op>) -> Result<(), TestCaseError>{
+        let mut db = ProtoDB::default();
+        let mut model = Model::default();
+        for op in ops{
+            match op{
+                Op::Checkpoint => {
+                    let s = model.current().clone();
+                    model.layers.push(s);
+                    prop_assert_eq!(db.checkpoint().unwrap(), model.layers.len() as u32);
+                },
+                Op::Revert => {
+                    match model.layers.pop(){
+                        None => {
+                            prop_assert!(db.revert_checkpoint().is_err());
+                        },
+                        Some(_) => {
+                            prop_assert_eq!(db.revert_checkpoint().unwrap(), model.layers.len() as u32);
+                        }
+                    }
+                },
+                Op::Collapse => {
+                    if let Some(top) = model.layers.pop(){
+                        model.layers.clear();
+                        model.layers.push(top);
+                    }
+                    prop_assert!(db.collapse_checkpoints().is_ok());
+                },
+                Op::Commit => {
+                    let mut s = model.current().clone();
+                    s.written.clear();
+                    s.touched.clear();
+                    model.committed = s;
+                    model.layers.clear();
+                    prop_assert!(db.commit().is_ok());
+                    prop_assert_eq!(db.state_root(), compute_state_root(&nest(&model.committed.values)));
+                },
+                Op::Clear => {
+                    model.layers.clear();
+                    db.clear_checkpoints();
+                },
+                Op::Read(a, k) => {
+                    let expected = model.current().values.get(&(a, k)).cloned();
+                    let committed = model.committed.values.get(&(a, k)).cloned();
+                    if let Some(top) = model.layers.last_mut(){
+                        if !top.written.contains(&(a, k)){
+                            top.touched.entry((a, k)).or_insert(committed);
+                        }
+                    }
+                    prop_assert_eq!(db.read_key(&address(a), &[k]).ok(), expected);
+                },
+                Op::Write(a, k, v) => {
+                    match model.layers.last_mut(){
+                        None => {
+                            prop_assert!(db.write_key(&address(a), &[k], &v).is_err());
+                        },
+                        Some(top) => {
+                            top.values.insert((a, k), v.clone());
+                            top.written.insert((a, k));
+                            prop_assert!(db.write_key(&address(a), &[k], &v).is_ok());
+                        }
+                    }
+                },
+                Op::ReadRent(a, k) => {
+                    let expected = model.current().rents.get(&(a, k)).cloned();
+                    prop_assert_eq!(db.read_rent(&address(a), &[k]).unwrap(), expected);
+                },
+                Op::Scan(a) => {
+                    let mut expected: Vec<(Vec<u8>, Vec<u8>)> = model.current().values.iter()
+                        .filter(|((a2, _), _)| *a2 == a)
+                        .map(|((_, k), v)| (vec![*k], v.clone()))
+                        .collect();
+                    expected.sort();
+                    prop_assert_eq!(db.scan_keys(&address(a), &[]).unwrap(), expected);
+                },
+                Op::Usage(a) => {
+                    let mut expected = StorageUsage::default();
+                    for (_, v) in model.current().values.iter().filter(|((a2, _), _)| *a2 == a){
+                        expected.keys += 1;
+                        expected.bytes += 1 + v.len() as u64;
+                    }
+                    prop_assert_eq!(db.storage_usage(&address(a)), expected);
+                },
+                Op::WriteRent(a, k, r) => {
+                    match model.layers.last_mut(){
+                        None => {
+                            prop_assert!(db.write_rent(&address(a), &[k], r).is_err());
+                        },
+                        Some(top) => {
+                            top.rents.insert((a, k), r);
+                            prop_assert!(db.write_rent(&address(a), &[k], r).is_ok());
+                        }
+                    }
+                },
+                Op::Differences => {
+                    if let Some(top) = model.layers.pop(){
+                        model.layers.clear();
+                        model.layers.push(top);
+                    }
+                    let s = model.current();
+                    let writes: HashMap<(u8, u8), Vec<u8>> = s.written.iter().map(|key| (*key, s.values[key].clone())).collect();
+                    let diff = db.compute_state_differences().unwrap();
+                    prop_assert_eq!(diff.writes, nest(&writes));
+                    prop_assert_eq!(diff.reads, nest(&s.touched));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn protodb_matches_model(ops in proptest::collection::vec(op_strategy(), 0..64)){
+            check_ops(ops)?;
+        }
+    }
+
+    #[test]
+    fn revert_removes_new_keys(){
+        //reverting a checkpoint removes the keys it created, which must remove them from the storage usage
+        check_ops(vec![
+            Op::Checkpoint,
+            Op::Write(0, 0, vec![1, 2]),
+            Op::Checkpoint,
+            Op::Write(0, 1, vec![3]),
+            Op::Write(0, 0, vec![]),
+            Op::Usage(0),
+            Op::Revert,
+            Op::Usage(0),
+            Op::Revert,
+            Op::Usage(0),
+            Op::Checkpoint,
+            Op::Write(0, 0, vec![4]),
+            Op::Commit,
+            Op::Checkpoint,
+            Op::Write(0, 0, vec![5, 6]),
+            Op::Write(0, 2, vec![7]),
+            Op::Revert,
+            Op::Usage(0),
+            Op::Checkpoint,
+            Op::Write(0, 3, vec![]),
+            Op::Clear,
+            Op::Usage(0),
+            Op::Read(0, 3)
+        ]).unwrap();
+    }
+}
@@ -0,0 +1,285 @@
+use std::fmt;
+use crate::crypto::double_sha256;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError{
+    /// The text contains a character which is not part of the encoding's alphabet, or mixes upper and lower case
+    InvalidCharacter,
+    /// The checksum does not match the data
+    InvalidChecksum,
+    /// The text is too short or its data does not fill a whole number of bytes
+    InvalidLength
+}
+
+impl fmt::Display for DecodeError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            DecodeError::InvalidCharacter => {
+                write!(f, "invalid character")
+            },
+            DecodeError::InvalidChecksum => {
+                write!(f, "invalid checksum")
+            },
+            DecodeError::InvalidLength => {
+                write!(f, "invalid length")
+            }
+        }
+    }
+}
+
+/// Encodes data as lowercase hex
+pub fn to_hex(data: &[u8]) -> String{
+    let mut s = String::with_capacity(data.len() * 2);
+    for b in data{
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+/// Decodes hex of either case, optionally prefixed with "0x". Returns None if the hex is invalid
+pub fn from_hex(hex: &str) -> Option<Vec<u8>>{
+    let hex = if hex.starts_with("0x"){
+        &hex[2..]
+    }else{
+        hex
+    };
+    if hex.len() % 2 != 0 || !hex.bytes().all(|c| c.is_ascii_hexdigit()){
+        return None;
+    }
+    let mut data = Vec::with_capacity(hex.len() / 2);
+    for i in (0..hex.len()).step_by(2){
+        data.push(u8::from_str_radix(&hex[i..i + 2], 16).ok()?);
+    }
+    Some(data)
+}
+
+
+const BASE58_ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Encodes data using the Bitcoin base58 alphabet. Each leading zero byte is encoded as a leading '1'
+pub fn to_base58(data: &[u8]) -> String{
+    let zeros = data.iter().take_while(|b| **b == 0).count();
+    //base58 digits of the remaining data, least significant first
+    let mut digits: Vec<u8> = vec![];
+    for b in data[zeros..].iter(){
+        let mut carry = *b as u32;
+        for d in digits.iter_mut(){
+            carry += (*d as u32) << 8;
+            *d = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0{
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    let mut s = String::with_capacity(zeros + digits.len());
+    for _ in 0..zeros{
+        s.push('1');
+    }
+    for d in digits.iter().rev(){
+        s.push(BASE58_ALPHABET[*d as usize] as char);
+    }
+    s
+}
+
+pub fn from_base58(text: &str) -> Result<Vec<u8>, DecodeError>{
+    let zeros = text.bytes().take_while(|c| *c == b'1').count();
+    //bytes of the remaining text, least significant first
+    let mut bytes: Vec<u8> = vec![];
+    for c in text.bytes().skip(zeros){
+        let mut carry = match BASE58_ALPHABET.iter().position(|a| *a == c){
+            None => {
+                return Err(DecodeError::InvalidCharacter);
+            },
+            Some(d) => {
+                d as u32
+            }
+        };
+        for b in bytes.iter_mut(){
+            carry += (*b as u32) * 58;
+            *b = (carry & 0xFF) as u8;
+            carry >>= 8;
+        }
+        while carry > 0{
+            bytes.push((carry & 0xFF) as u8);
+            carry >>= 8;
+        }
+    }
+    let mut data = vec![0u8; zeros];
+    data.extend(bytes.iter().rev());
+    Ok(data)
+}
+
+/// Encodes data using base58 followed by a 4 byte double sha256 checksum, as used by Bitcoin and Qtum addresses
+pub fn to_base58check(data: &[u8]) -> String{
+    let mut payload = data.to_vec();
+    payload.extend_from_slice(&double_sha256(data)[0..4]);
+    to_base58(&payload)
+}
+
+pub fn from_base58check(text: &str) -> Result<Vec<u8>, DecodeError>{
+    let mut payload = from_base58(text)?;
+    if payload.len() < 4{
+        return Err(DecodeError::InvalidLength);
+    }
+    let checksum = payload.split_off(payload.len() - 4);
+    if double_sha256(&payload)[0..4] != checksum[..]{
+        return Err(DecodeError::InvalidChecksum);
+    }
+    Ok(payload)
+}
+
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+fn bech32_polymod(values: &[u8]) -> u32{
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    let mut checksum: u32 = 1;
+    for v in values{
+        let top = checksum >> 25;
+        checksum = ((checksum & 0x1ffffff) << 5) ^ (*v as u32);
+        for (i, g) in GENERATOR.iter().enumerate(){
+            if (top >> i) & 1 == 1{
+                checksum ^= g;
+            }
+        }
+    }
+    checksum
+}
+
+fn bech32_hrp_expand(hrp: &str) -> Vec<u8>{
+    let mut values: Vec<u8> = hrp.bytes().map(|c| c >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|c| c & 31));
+    values
+}
+
+/// Regroups bits from groups of `from` bits into groups of `to` bits.
+/// When padding, leftover bits are padded with zeros. Otherwise leftover bits must be zero and fewer than `from`
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Result<Vec<u8>, DecodeError>{
+    let mut accumulator: u32 = 0;
+    let mut bits: u32 = 0;
+    let mut converted = vec![];
+    let max = (1u32 << to) - 1;
+    for v in data{
+        accumulator = (accumulator << from) | (*v as u32);
+        bits += from;
+        while bits >= to{
+            bits -= to;
+            converted.push(((accumulator >> bits) & max) as u8);
+        }
+    }
+    if pad{
+        if bits > 0{
+            converted.push(((accumulator << (to - bits)) & max) as u8);
+        }
+    }else if bits >= from || ((accumulator << (to - bits)) & max) != 0{
+        return Err(DecodeError::InvalidLength);
+    }
+    Ok(converted)
+}
+
+/// Encodes data using bech32 (BIP173) with the given human readable part, which should be lowercase
+pub fn to_bech32(hrp: &str, data: &[u8]) -> String{
+    let values = convert_bits(data, 8, 5, true).unwrap();
+    let mut checked = bech32_hrp_expand(hrp);
+    checked.extend_from_slice(&values);
+    checked.extend_from_slice(&[0; 6]);
+    let polymod = bech32_polymod(&checked) ^ 1;
+    let mut s = String::with_capacity(hrp.len() + 1 + values.len() + 6);
+    s.push_str(hrp);
+    s.push('1');
+    for v in values.iter(){
+        s.push(BECH32_CHARSET[*v as usize] as char);
+    }
+    for i in 0..6{
+        s.push(BECH32_CHARSET[((polymod >> (5 * (5 - i))) & 31) as usize] as char);
+    }
+    s
+}
+
+/// Decodes bech32 text into its lowercase human readable part and data.
+/// The BIP173 limit of 90 characters is not enforced so that long addresses can be encoded
+pub fn from_bech32(text: &str) -> Result<(String, Vec<u8>), DecodeError>{
+    if text.bytes().any(|c| c < 33 || c > 126){
+        return Err(DecodeError::InvalidCharacter);
+    }
+    let lower = text.to_ascii_lowercase();
+    if lower != text && text.to_ascii_uppercase() != text{
+        return Err(DecodeError::InvalidCharacter);
+    }
+    let separator = match lower.rfind('1'){
+        None => {
+            return Err(DecodeError::InvalidCharacter);
+        },
+        Some(i) => {
+            i
+        }
+    };
+    if separator == 0 || lower.len() - separator - 1 < 6{
+        return Err(DecodeError::InvalidLength);
+    }
+    let hrp = &lower[..separator];
+    let mut values = vec![];
+    for c in lower[separator + 1..].bytes(){
+        match BECH32_CHARSET.iter().position(|a| *a == c){
+            None => {
+                return Err(DecodeError::InvalidCharacter);
+            },
+            Some(v) => {
+                values.push(v as u8);
+            }
+        }
+    }
+    let mut checked = bech32_hrp_expand(hrp);
+    checked.extend_from_slice(&values);
+    if bech32_polymod(&checked) != 1{
+        return Err(DecodeError::InvalidChecksum);
+    }
+    values.truncate(values.len() - 6);
+    Ok((hrp.to_string(), convert_bits(&values, 5, 8, false)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_hex(){
+        assert_eq!(to_hex(&[0xAB, 0x01]), "ab01");
+        assert_eq!(from_hex("ab01"), Some(vec![0xAB, 0x01]));
+        assert_eq!(from_hex("0xAB01"), Some(vec![0xAB, 0x01]));
+        assert_eq!(from_hex(""), Some(vec![]));
+        assert_eq!(from_hex("abc"), None);
+        assert_eq!(from_hex("zz"), None);
+        assert_eq!(from_hex("+1"), None);
+    }
+    #[test]
+    fn test_base58(){
+        assert_eq!(to_base58(&[]), "");
+        assert_eq!(to_base58(&[0, 0, 1]), "112");
+        assert_eq!(to_base58(b"hello world"), "StV1DL6CwTryKyV");
+        assert_eq!(from_base58("StV1DL6CwTryKyV").unwrap(), b"hello world".to_vec());
+        assert_eq!(from_base58("112").unwrap(), vec![0, 0, 1]);
+        assert_eq!(from_base58("0OIl"), Err(DecodeError::InvalidCharacter));
+        //the version 0 Bitcoin address of an all zero hash160
+        assert_eq!(to_base58check(&[0; 21]), "1111111111111111111114oLvT2");
+        assert_eq!(from_base58check("1111111111111111111114oLvT2").unwrap(), vec![0; 21]);
+        assert_eq!(from_base58check("1111111111111111111114oLvT3"), Err(DecodeError::InvalidChecksum));
+        assert_eq!(from_base58check("11"), Err(DecodeError::InvalidLength));
+    }
+    #[test]
+    fn test_bech32(){
+        //test vectors from BIP173
+        assert_eq!(from_bech32("A12UEL5L").unwrap(), ("a".to_string(), vec![]));
+        assert!(from_bech32("abcdef1qpzry9x8gf2tvdw0s3jn54khce6mua7lmqqqxw").is_ok());
+        assert_eq!(from_bech32("A1G7SGD8"), Err(DecodeError::InvalidChecksum));
+        assert_eq!(from_bech32("pzry9x0s0muk"), Err(DecodeError::InvalidCharacter));
+        assert_eq!(from_bech32("10a06t8"), Err(DecodeError::InvalidLength));
+        assert_eq!(from_bech32("A12uEL5L"), Err(DecodeError::InvalidCharacter));
+        assert_eq!(to_bech32("a", &[]), "a12uel5l");
+        let data: Vec<u8> = (0..40).collect();
+        let encoded = to_bech32("n2", &data);
+        assert_eq!(from_bech32(&encoded).unwrap(), ("n2".to_string(), data.clone()));
+        assert_eq!(from_bech32(&encoded.to_uppercase()).unwrap(), ("n2".to_string(), data));
+    }
+}
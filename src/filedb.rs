@@ -0,0 +1,517 @@
+extern crate neutron_star_constants;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+use neutron_star_constants::*;
+use crate::db::*;
+use crate::merkle::StateProof;
+use crate::snapshot::DBSnapshot;
+use crate::crypto::sha256;
+
+/// Identifies a FileDB log and the version of its format.
+/// Version 02 stores 20 byte addresses under their own data rather than a hash of it.
+/// Version 03 adds a checksum of each record's length, so logs of older versions are rejected
+pub const FILEDB_MAGIC: &[u8; 8] = b"NTRNDB03";
+
+/// The number of bytes of sha256(length) stored to check the length of a record
+const HEADER_CHECKSUM_SIZE: usize = 4;
+
+const ENTRY_VALUE: u8 = 0;
+const ENTRY_RENT: u8 = 1;
+
+/*
+## FileDB log format
+
+The file begins with FILEDB_MAGIC, followed by one record per commit:
+
+* payload length (LE u32)
+* the first 4 bytes of sha256 of the payload length bytes
+* sha256 of the payload
+* payload
+
+The payload is a sequence of entries, each being a tag byte followed by the address version (LE u32), the address data, the key length (LE u32) and the key.
+A value entry (tag 0) ends with the value length (LE u32) and the value. A rent entry (tag 1) ends with the paid until height (LE u32).
+
+A commit is only considered durable once its entire record has been synced to disk.
+When opening, a trailing record whose header is incomplete, or whose valid header describes a record running past the end of the log, was interrupted by a crash and is truncated away.
+Any record which fails either checksum means the log is corrupted, so opening fails rather than discarding commits.
+The length is checked before it is trusted, so a corrupted length can not make later records look like an interrupted commit.
+*/
+
+/// A NeutronDB which persists committed state to an append-only log on disk.
+/// The log is replayed into memory when opened, so reads never touch the disk and checkpoints behave exactly as in ProtoDB
+pub struct FileDB{
+    memory: ProtoDB,
+    file: File,
+    /// The length of the log up to the end of the last complete record
+    length: u64
+}
+
+impl FileDB{
+    /// Opens the log at the given path, creating it if it does not exist, and replays all complete records
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FileDB>{
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        let mut contents = vec![];
+        file.read_to_end(&mut contents)?;
+        if contents.len() == 0{
+            file.write_all(FILEDB_MAGIC)?;
+            file.sync_all()?;
+            contents.extend_from_slice(FILEDB_MAGIC);
+        }
+        if contents.len() < FILEDB_MAGIC.len() || &contents[0..FILEDB_MAGIC.len()] != FILEDB_MAGIC{
+            if contents.starts_with(&FILEDB_MAGIC[0..6]){
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported FileDB log version"));
+            }
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a FileDB log"));
+        }
+        let mut memory = ProtoDB::default();
+        memory.checkpoint().unwrap();
+        let mut position = FILEDB_MAGIC.len();
+        loop{
+            let mut reader = Reader{
+                data: &contents,
+                position: position
+            };
+            let payload = match read_record(&mut reader){
+                Record::Incomplete => {
+                    break;
+                },
+                Record::Corrupted => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "corrupted FileDB record"));
+                },
+                Record::Valid(p) => {
+                    p
+                }
+            };
+            if replay_payload(&mut memory, payload).is_none(){
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed FileDB record"));
+            }
+            position = reader.position;
+        }
+        if position < contents.len(){
+            file.set_len(position as u64)?;
+            file.sync_all()?;
+        }
+        if memory.commit().is_err(){
+            return Err(io::Error::new(io::ErrorKind::Other, "error replaying FileDB log"));
+        }
+        Ok(FileDB{
+            memory: memory,
+            file: file,
+            length: position as u64
+        })
+    }
+    fn append_record(&mut self, payload: &[u8]) -> io::Result<()>{
+        let mut record = (payload.len() as u32).to_le_bytes().to_vec();
+        record.extend_from_slice(&header_checksum(&record));
+        record.extend_from_slice(&sha256(payload));
+        record.extend_from_slice(payload);
+        self.file.write_all(&record)?;
+        self.file.sync_data()?;
+        self.length += record.len() as u64;
+        Ok(())
+    }
+}
+
+impl NeutronDB for FileDB{
+    fn read_key(&mut self, address: &NeutronShortAddress, key: &[u8]) -> Result<Vec<u8>, NeutronDBError>{
+        self.memory.read_key(address, key)
+    }
+    fn write_key(&mut self, address: &NeutronShortAddress, key: &[u8], value: &[u8]) -> Result<(), NeutronDBError>{
+        self.memory.write_key(address, key, value)
+    }
+    fn checkpoint(&mut self) -> Result<u32, NeutronDBError>{
+        self.memory.checkpoint()
+    }
+    fn collapse_checkpoints(&mut self) -> Result<(), NeutronDBError>{
+        self.memory.collapse_checkpoints()
+    }
+    fn revert_checkpoint(&mut self) -> Result<u32, NeutronDBError>{
+        self.memory.revert_checkpoint()
+    }
+    fn clear_checkpoints(&mut self){
+        self.memory.clear_checkpoints()
+    }
+    /// Writes the committed state to the log and syncs it before applying it in memory.
+    /// If writing fails the log is truncated back to its previous length and the state remains uncommitted
+    fn commit(&mut self) -> Result<(), NeutronDBError>{
+        let writes = self.memory.compute_state_differences()?.writes;
+        let rents = self.memory.uncommitted_rents()?;
+        let payload = encode_payload(&writes, &rents);
+        if payload.len() > 0{
+            if payload.len() > u32::max_value() as usize{
+                return Err(NeutronDBError::Unrecoverable);
+            }
+            if self.append_record(&payload).is_err(){
+                let _ = self.file.set_len(self.length);
+                return Err(NeutronDBError::Unrecoverable);
+            }
+        }
+        self.memory.commit()
+    }
+    fn read_rent(&mut self, address: &NeutronShortAddress, key: &[u8]) -> Result<Option<u32>, NeutronDBError>{
+        self.memory.read_rent(address, key)
+    }
+    fn write_rent(&mut self, address: &NeutronShortAddress, key: &[u8], paid_until: u32) -> Result<(), NeutronDBError>{
+        self.memory.write_rent(address, key, paid_until)
+    }
+    fn state_root(&self) -> [u8; 32]{
+        self.memory.state_root()
+    }
+    fn scan_keys(&self, address: &NeutronShortAddress, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, NeutronDBError>{
+        self.memory.scan_keys(address, prefix)
+    }
+    fn prove_key(&self, address: &NeutronShortAddress, key: &[u8]) -> Result<StateProof, NeutronDBError>{
+        self.memory.prove_key(address, key)
+    }
+    /// History is only kept in memory, so it begins again from the current state each time the log is opened
+    fn enable_history(&mut self, pruning: HistoryPruning){
+        self.memory.enable_history(pruning)
+    }
+    fn set_block_height(&mut self, height: u32){
+        self.memory.set_block_height(height)
+    }
+    fn read_key_at(&self, address: &NeutronShortAddress, key: &[u8], height: u32) -> Result<Option<Vec<u8>>, NeutronDBError>{
+        self.memory.read_key_at(address, key, height)
+    }
+    fn earliest_history_height(&self) -> Option<u32>{
+        self.memory.earliest_history_height()
+    }
+    fn storage_usage(&self, address: &NeutronShortAddress) -> StorageUsage{
+        self.memory.storage_usage(address)
+    }
+    fn snapshot(&self) -> DBSnapshot{
+        self.memory.snapshot()
+    }
+    fn compute_state_differences(&mut self) -> Result<StateDifferences, NeutronDBError>{
+        self.memory.compute_state_differences()
+    }
+}
+
+fn encode_entry_header(payload: &mut Vec<u8>, tag: u8, address: &NeutronShortAddress, key: &[u8]){
+    payload.push(tag);
+    payload.extend_from_slice(&address.version.to_le_bytes());
+    payload.extend_from_slice(&address.data);
+    payload.extend_from_slice(&(key.len() as u32).to_le_bytes());
+    payload.extend_from_slice(key);
+}
+
+fn encode_payload(writes: &HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>, rents: &HashMap<NeutronShortAddress, HashMap<Vec<u8>, u32>>) -> Vec<u8>{
+    let mut payload = vec![];
+    for (address, kv) in writes.iter(){
+        for (key, value) in kv.iter(){
+            encode_entry_header(&mut payload, ENTRY_VALUE, address, key);
+            payload.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            payload.extend_from_slice(value);
+        }
+    }
+    for (address, kv) in rents.iter(){
+        for (key, paid_until) in kv.iter(){
+            encode_entry_header(&mut payload, ENTRY_RENT, address, key);
+            payload.extend_from_slice(&paid_until.to_le_bytes());
+        }
+    }
+    payload
+}
+
+struct Reader<'a>{
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a>{
+    fn take(&mut self, count: usize) -> Option<&'a [u8]>{
+        if self.data.len() - self.position < count{
+            return None;
+        }
+        let s = &self.data[self.position..self.position + count];
+        self.position += count;
+        Some(s)
+    }
+    fn take_u32(&mut self) -> Option<u32>{
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Some(u32::from_le_bytes(bytes))
+    }
+    fn done(&self) -> bool{
+        self.position == self.data.len()
+    }
+}
+
+/// The result of reading the next record of a log
+enum Record<'a>{
+    /// The record's payload, which matches both of its checksums
+    Valid(&'a [u8]),
+    /// The log ends partway through the record's header, or partway through a record with a valid header
+    Incomplete,
+    /// The record fails one of its checksums
+    Corrupted
+}
+
+fn header_checksum(length: &[u8]) -> [u8; HEADER_CHECKSUM_SIZE]{
+    let mut checksum = [0u8; HEADER_CHECKSUM_SIZE];
+    checksum.copy_from_slice(&sha256(length)[0..HEADER_CHECKSUM_SIZE]);
+    checksum
+}
+
+fn read_record<'a>(reader: &mut Reader<'a>) -> Record<'a>{
+    let length = match reader.take(4){
+        None => {
+            return Record::Incomplete;
+        },
+        Some(l) => {
+            l
+        }
+    };
+    match reader.take(HEADER_CHECKSUM_SIZE){
+        None => {
+            return Record::Incomplete;
+        },
+        Some(c) => {
+            if c != &header_checksum(length)[..]{
+                return Record::Corrupted;
+            }
+        }
+    }
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(length);
+    let length = u32::from_le_bytes(bytes) as usize;
+    let checksum = match reader.take(32){
+        None => {
+            return Record::Incomplete;
+        },
+        Some(c) => {
+            c
+        }
+    };
+    match reader.take(length){
+        None => {
+            Record::Incomplete
+        },
+        Some(payload) => {
+            if &sha256(payload)[..] == checksum{
+                Record::Valid(payload)
+            }else{
+                Record::Corrupted
+            }
+        }
+    }
+}
+
+fn replay_payload(db: &mut ProtoDB, payload: &[u8]) -> Option<()>{
+    let mut reader = Reader{
+        data: payload,
+        position: 0
+    };
+    while !reader.done(){
+        let tag = reader.take(1)?[0];
+        let mut address = NeutronShortAddress::default();
+        address.version = reader.take_u32()?;
+        let data_length = address.data.len();
+        address.data.copy_from_slice(reader.take(data_length)?);
+        let key_length = reader.take_u32()? as usize;
+        let key = reader.take(key_length)?;
+        match tag{
+            ENTRY_VALUE => {
+                let value_length = reader.take_u32()? as usize;
+                let value = reader.take(value_length)?;
+                db.write_key(&address, key, value).ok()?;
+            },
+            ENTRY_RENT => {
+                let paid_until = reader.take_u32()?;
+                db.write_rent(&address, key, paid_until).ok()?;
+            },
+            _ => {
+                return None;
+            }
+        }
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    fn temp_log(name: &str) -> PathBuf{
+        let mut path = std::env::temp_dir();
+        path.push(format!("neutron-filedb-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+    fn record_end(contents: &[u8], position: usize) -> usize{
+        let mut reader = Reader{
+            data: contents,
+            position: position
+        };
+        match read_record(&mut reader){
+            Record::Valid(_) => {
+                reader.position
+            },
+            _ => {
+                panic!("expected a valid record");
+            }
+        }
+    }
+    #[test]
+    fn test_persistence(){
+        let path = temp_log("persistence");
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let root;
+        {
+            let mut db = FileDB::open(&path).unwrap();
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[8, 8]).is_ok());
+            assert!(db.write_rent(&a, &[1], 10).is_ok());
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[2], &[9]).is_ok());
+            assert!(db.revert_checkpoint().is_ok());
+            assert!(db.commit().is_ok());
+            //uncommitted state is never written
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[3], &[7]).is_ok());
+            root = db.state_root();
+        }
+        let mut db = FileDB::open(&path).unwrap();
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![8, 8]);
+        assert_eq!(db.read_rent(&a, &[1]).unwrap(), Some(10));
+        assert!(db.read_key(&a, &[2]).is_err());
+        assert!(db.read_key(&a, &[3]).is_err());
+        assert_eq!(db.state_root(), root);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_torn_commit(){
+        let path = temp_log("torn");
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let length;
+        {
+            let mut db = FileDB::open(&path).unwrap();
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[8]).is_ok());
+            assert!(db.commit().is_ok());
+            length = db.length;
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[9]).is_ok());
+            assert!(db.commit().is_ok());
+        }
+        //simulate a crash partway through writing the second record
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(length + 10).unwrap();
+        drop(file);
+        let mut db = FileDB::open(&path).unwrap();
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![8]);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), length);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[10]).is_ok());
+        assert!(db.commit().is_ok());
+        drop(db);
+        let mut db = FileDB::open(&path).unwrap();
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![10]);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_corrupted_record(){
+        let path = temp_log("corrupted");
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let length;
+        {
+            let mut db = FileDB::open(&path).unwrap();
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[8]).is_ok());
+            assert!(db.commit().is_ok());
+            length = db.length;
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[9]).is_ok());
+            assert!(db.commit().is_ok());
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[2], &[10]).is_ok());
+            assert!(db.commit().is_ok());
+        }
+        let total = std::fs::metadata(&path).unwrap().len();
+        //flip the last byte of the second record's payload, which is followed by the third record
+        let mut contents = std::fs::read(&path).unwrap();
+        let end = record_end(&contents, length as usize);
+        contents[end - 1] ^= 0xFF;
+        std::fs::write(&path, &contents).unwrap();
+        match FileDB::open(&path){
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            },
+            Ok(_) => {
+                panic!("expected a corrupted log to fail to open");
+            }
+        }
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), total);
+
+        //a complete final record which fails its checksum is corrupted too, rather than interrupted
+        contents.truncate(end);
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(FileDB::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), end as u64);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_corrupted_length(){
+        let path = temp_log("corrupted-length");
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let length;
+        {
+            let mut db = FileDB::open(&path).unwrap();
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[8]).is_ok());
+            assert!(db.commit().is_ok());
+            length = db.length;
+            assert!(db.checkpoint().is_ok());
+            assert!(db.write_key(&a, &[1], &[9]).is_ok());
+            assert!(db.commit().is_ok());
+        }
+        let total = std::fs::metadata(&path).unwrap().len();
+        //make the first record claim to run past the end of the log
+        let mut contents = std::fs::read(&path).unwrap();
+        contents[FILEDB_MAGIC.len() + 2] ^= 0xFF;
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(FileDB::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), total);
+
+        //a shorter corrupted length is rejected as well
+        contents[FILEDB_MAGIC.len() + 2] ^= 0xFF;
+        contents[FILEDB_MAGIC.len()] ^= 0x01;
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(FileDB::open(&path).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), total);
+
+        //the intact log still opens, with both records
+        contents[FILEDB_MAGIC.len()] ^= 0x01;
+        std::fs::write(&path, &contents).unwrap();
+        assert_eq!(record_end(&contents, length as usize), total as usize);
+        let mut db = FileDB::open(&path).unwrap();
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![9]);
+        std::fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn test_old_format(){
+        let path = temp_log("old-format");
+        std::fs::write(&path, b"NTRNDB01").unwrap();
+        match FileDB::open(&path){
+            Err(e) => {
+                assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+            },
+            Ok(_) => {
+                panic!("expected a log of an older format to fail to open");
+            }
+        }
+        assert_eq!(std::fs::read(&path).unwrap(), b"NTRNDB01".to_vec());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,401 @@
+extern crate neutron_star_constants;
+extern crate elf;
+extern crate serde_json;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde_derive::{Serialize, Deserialize};
+use neutron_star_constants::*;
+use crate::addressing::*;
+use crate::db::*;
+use crate::encoding::*;
+use crate::hypervisor::X86Interface;
+use crate::testbench::Testbench;
+
+/*
+## Genesis files
+
+A genesis file is a JSON description of a Testbench's initial state, meant to be readable in code review:
+
+    {
+        "block": {"creator": "n11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqq8lr24ce3", "gas_limit": 1000000, "difficulty": 0, "height": 10, "previous_time": 0, "previous_hashes": []},
+        "balances": {"n11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqq8lr24ce3": 5000},
+        "accounts": {
+            "ns21qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqp7q9sx0": {
+                "elf": "contracts/token.elf",
+                "storage": {"6b6579": "76616c7565"}
+            }
+        }
+    }
+
+Addresses use the text formats of the addressing module. Accounts are keyed by short address, as that is how the database stores them,
+while balances are keyed by full address. All keys and values are hex.
+
+A contract's code can be given either as the path of an ELF executable or as hex "code" and "data" sections.
+Contracts are installed directly into state without running their deployment code, so that exporting and importing a genesis file is lossless.
+"protected" holds state of any other storage space, such as contract nonces, with keys including their space byte.
+*/
+
+#[derive(Debug)]
+pub enum GenesisError{
+    Io(io::Error),
+    Json(String),
+    InvalidAddress(String),
+    InvalidHex(String),
+    InvalidElf(String),
+    /// An account gave both an ELF path and hex sections, or only one of the code and data sections
+    InvalidContract(String),
+    Database
+}
+
+impl fmt::Display for GenesisError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            GenesisError::Io(e) => {
+                write!(f, "Genesis I/O error: {}", e)
+            },
+            GenesisError::Json(e) => {
+                write!(f, "Genesis JSON error: {}", e)
+            },
+            GenesisError::InvalidAddress(a) => {
+                write!(f, "Invalid genesis address: {}", a)
+            },
+            GenesisError::InvalidHex(h) => {
+                write!(f, "Invalid genesis hex: {}", h)
+            },
+            GenesisError::InvalidElf(e) => {
+                write!(f, "Invalid genesis ELF: {}", e)
+            },
+            GenesisError::InvalidContract(a) => {
+                write!(f, "Invalid genesis contract: {}", a)
+            },
+            GenesisError::Database => {
+                write!(f, "Error writing genesis state into the database")
+            }
+        }
+    }
+}
+
+impl error::Error for GenesisError{
+}
+
+impl From<io::Error> for GenesisError{
+    fn from(e: io::Error) -> GenesisError{
+        GenesisError::Io(e)
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenesisBlock{
+    pub creator: String,
+    pub gas_limit: u64,
+    pub difficulty: u64,
+    pub height: u32,
+    pub previous_time: u64,
+    pub previous_hashes: Vec<String>
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenesisAccount{
+    /// The path of an ELF executable to take the code and data sections from. Only used when importing
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elf: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    /// User storage, as seen by the contract
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub protected: BTreeMap<String, String>
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Genesis{
+    pub block: GenesisBlock,
+    pub balances: BTreeMap<String, u64>,
+    pub accounts: BTreeMap<String, GenesisAccount>
+}
+
+fn parse_address(s: &str) -> Result<NeutronAddress, GenesisError>{
+    match s.parse::<NeutronAddress>(){
+        Err(e) => {
+            Err(GenesisError::InvalidAddress(format!("{}: {}", s, e)))
+        },
+        Ok(a) => {
+            Ok(a)
+        }
+    }
+}
+
+fn parse_genesis_short_address(s: &str) -> Result<NeutronShortAddress, GenesisError>{
+    match parse_short_address(s){
+        Err(e) => {
+            Err(GenesisError::InvalidAddress(format!("{}: {}", s, e)))
+        },
+        Ok(a) => {
+            Ok(a)
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Result<Vec<u8>, GenesisError>{
+    match from_hex(s){
+        None => {
+            Err(GenesisError::InvalidHex(s.to_string()))
+        },
+        Some(v) => {
+            Ok(v)
+        }
+    }
+}
+
+/// Reads the code (.text) and data (.data) sections of an ELF executable
+fn read_elf_sections(path: &str) -> Result<(Vec<u8>, Vec<u8>), GenesisError>{
+    let file = match elf::File::open_path(path){
+        Err(e) => {
+            return Err(GenesisError::InvalidElf(format!("{}: {:?}", path, e)));
+        },
+        Ok(f) => {
+            f
+        }
+    };
+    match (file.get_section(".text"), file.get_section(".data")){
+        (Some(text), Some(data)) => {
+            Ok((text.data.clone(), data.data.clone()))
+        },
+        _ => {
+            Err(GenesisError::InvalidElf(format!("{}: missing .text or .data section", path)))
+        }
+    }
+}
+
+impl GenesisAccount{
+    /// The code and data sections of the account's contract, if it has one
+    fn sections(&self, address: &str) -> Result<Option<(Vec<u8>, Vec<u8>)>, GenesisError>{
+        match (&self.elf, &self.code, &self.data){
+            (None, None, None) => {
+                Ok(None)
+            },
+            (Some(path), None, None) => {
+                Ok(Some(read_elf_sections(path)?))
+            },
+            (None, Some(code), Some(data)) => {
+                Ok(Some((parse_hex(code)?, parse_hex(data)?)))
+            },
+            _ => {
+                Err(GenesisError::InvalidContract(address.to_string()))
+            }
+        }
+    }
+}
+
+impl Genesis{
+    pub fn from_json(json: &str) -> Result<Genesis, GenesisError>{
+        match serde_json::from_str(json){
+            Err(e) => {
+                Err(GenesisError::Json(e.to_string()))
+            },
+            Ok(g) => {
+                Ok(g)
+            }
+        }
+    }
+    /// Encodes the genesis as pretty printed JSON. Maps are ordered so that the same state always gives the same output
+    pub fn to_json(&self) -> String{
+        serde_json::to_string_pretty(self).unwrap()
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Genesis, GenesisError>{
+        Genesis::from_json(&fs::read_to_string(path)?)
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), GenesisError>{
+        fs::write(path, self.to_json())?;
+        Ok(())
+    }
+
+    /// Sets a Testbench's block context and balances to those of the genesis, and commits the genesis state at the genesis block height.
+    /// The Testbench is expected to be newly created, see Testbench::from_genesis
+    pub fn install(&self, testbench: &mut Testbench) -> Result<(), GenesisError>{
+        let block = &self.block;
+        testbench.block.creator = if block.creator.is_empty(){
+            NeutronAddress::default()
+        }else{
+            parse_address(&block.creator)?
+        };
+        testbench.block.gas_limit = block.gas_limit;
+        testbench.block.difficulty = block.difficulty;
+        testbench.block.height = block.height;
+        testbench.block.previous_time = block.previous_time;
+        for hash in block.previous_hashes.iter(){
+            let h = parse_hex(hash)?;
+            if h.len() != 32{
+                return Err(GenesisError::InvalidHex(hash.to_string()));
+            }
+            let mut b = [0u8; 32];
+            b.copy_from_slice(&h);
+            testbench.block.previous_hashes.push(b);
+        }
+        for (address, balance) in self.balances.iter(){
+            testbench.balances.insert(parse_address(address)?, *balance);
+        }
+
+        let mut state = vec![];
+        for (address, account) in self.accounts.iter(){
+            let short = parse_genesis_short_address(address)?;
+            if let Some((code, data)) = account.sections(address)?{
+                state.push((short, vec![X86Interface::X86_SPACE, X86Interface::CODE_SECTION_SPACE, 0], code));
+                state.push((short, vec![X86Interface::X86_SPACE, X86Interface::DATA_SECTION_SPACE, 0], data));
+            }
+            for (key, value) in account.storage.iter(){
+                let mut k = vec![NEUTRONDB_USER_SPACE];
+                k.extend_from_slice(&parse_hex(key)?);
+                state.push((short, k, parse_hex(value)?));
+            }
+            for (key, value) in account.protected.iter(){
+                state.push((short, parse_hex(key)?, parse_hex(value)?));
+            }
+        }
+        if testbench.db.checkpoint().is_err(){
+            return Err(GenesisError::Database);
+        }
+        for (address, key, value) in state{
+            if testbench.db.write_key(&address, &key, &value).is_err(){
+                return Err(GenesisError::Database);
+            }
+        }
+        testbench.db.set_block_height(block.height);
+        if testbench.db.commit().is_err(){
+            return Err(GenesisError::Database);
+        }
+        Ok(())
+    }
+
+    /// Describes the committed state, balances and block context of a Testbench.
+    /// Contract code is always exported as hex sections and storage rent is not included
+    pub fn from_testbench(testbench: &Testbench) -> Genesis{
+        let block = &testbench.block;
+        let mut genesis = Genesis{
+            block: GenesisBlock{
+                creator: block.creator.to_string(),
+                gas_limit: block.gas_limit,
+                difficulty: block.difficulty,
+                height: block.height,
+                previous_time: block.previous_time,
+                previous_hashes: block.previous_hashes.iter().map(|h| to_hex(h)).collect()
+            },
+            balances: testbench.balances.iter().map(|(a, b)| (a.to_string(), *b)).collect(),
+            accounts: BTreeMap::new()
+        };
+        let code_key = [X86Interface::X86_SPACE, X86Interface::CODE_SECTION_SPACE, 0];
+        let data_key = [X86Interface::X86_SPACE, X86Interface::DATA_SECTION_SPACE, 0];
+        for entry in testbench.db.snapshot().entries{
+            //snapshots of a database always contain valid short addresses
+            let address = short_address_to_string(&entry.address().unwrap());
+            let value = match entry.value{
+                None => {
+                    continue;
+                },
+                Some(v) => {
+                    to_hex(&v)
+                }
+            };
+            let account = genesis.accounts.entry(address).or_insert_with(GenesisAccount::default);
+            if entry.key.first() == Some(&NEUTRONDB_USER_SPACE){
+                account.storage.insert(to_hex(&entry.key[1..]), value);
+            }else if entry.key[..] == code_key{
+                account.code = Some(value);
+            }else if entry.key[..] == data_key{
+                account.data = Some(value);
+            }else{
+                account.protected.insert(to_hex(&entry.key), value);
+            }
+        }
+        genesis
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    const SAMPLE: &str = r#"{
+        "block": {"creator": "n11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqq8lr24ce3", "gas_limit": 1000, "height": 10, "previous_hashes": ["0101010101010101010101010101010101010101010101010101010101010101"]},
+        "balances": {"n11qqqqqqqqqqqqqqqqqqqqqqqqqqqqqq8lr24ce3": 5000},
+        "accounts": {
+            "ns21qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqp7q9sx0": {
+                "code": "9090c3",
+                "data": "00",
+                "storage": {"6b6579": "76616c7565"}
+            },
+            "ns21qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqzsnsxgs": {
+                "storage": {"01": "02"},
+                "protected": {"0500": "aa"}
+            }
+        }
+    }"#;
+    #[test]
+    fn test_genesis_import(){
+        let genesis = Genesis::from_json(SAMPLE).unwrap();
+        let mut testbench = Testbench::default();
+        genesis.install(&mut testbench).unwrap();
+        assert_eq!(testbench.block.height, 10);
+        assert_eq!(testbench.block.gas_limit, 1000);
+        assert_eq!(testbench.block.previous_hashes, vec![[1u8; 32]]);
+        let mut creator = NeutronAddress{
+            version: 1,
+            data: vec![0; 20]
+        };
+        creator.data[19] = 0xFF;
+        assert_eq!(testbench.block.creator, creator);
+        assert_eq!(testbench.balances.get(&creator), Some(&5000));
+
+        let mut contract = NeutronShortAddress::default();
+        contract.version = 2;
+        contract.data[19] = 1;
+        assert_eq!(testbench.db.read_key(&contract, &[2, 1, 0]).unwrap(), vec![0x90, 0x90, 0xC3]);
+        assert_eq!(testbench.db.read_key(&contract, &[2, 2, 0]).unwrap(), vec![0]);
+        assert_eq!(testbench.db.read_key(&contract, b"_key").unwrap(), b"value".to_vec());
+        contract.data[19] = 2;
+        assert_eq!(testbench.db.read_key(&contract, &[5, 0]).unwrap(), vec![0xAA]);
+    }
+    #[test]
+    fn test_genesis_roundtrip(){
+        let genesis = Genesis::from_json(SAMPLE).unwrap();
+        let mut testbench = Testbench::default();
+        genesis.install(&mut testbench).unwrap();
+        let exported = Genesis::from_testbench(&testbench);
+        assert_eq!(exported, genesis);
+        assert_eq!(Genesis::from_json(&exported.to_json()).unwrap(), exported);
+        let mut rebuilt = Testbench::default();
+        exported.install(&mut rebuilt).unwrap();
+        assert_eq!(rebuilt.db.state_root(), testbench.db.state_root());
+    }
+    #[test]
+    fn test_genesis_errors(){
+        let mut genesis = Genesis::default();
+        let mut account = GenesisAccount::default();
+        account.code = Some("00".to_string());
+        genesis.accounts.insert("ns21qqqqqqqqqqqqqqqqqqqqqqqqqqqqqqqp7q9sx0".to_string(), account);
+        match genesis.install(&mut Testbench::default()){
+            Err(GenesisError::InvalidContract(_)) => {},
+            _ => {
+                panic!("expected an invalid contract error");
+            }
+        }
+        let mut genesis = Genesis::default();
+        genesis.accounts.insert("ns21qqqqjrmgfs".to_string(), GenesisAccount::default());
+        match genesis.install(&mut Testbench::default()){
+            Err(GenesisError::InvalidAddress(_)) => {},
+            _ => {
+                panic!("expected an invalid address error");
+            }
+        }
+        assert!(Genesis::from_json("{\"balances\": 5}").is_err());
+    }
+}
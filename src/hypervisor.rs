@@ -120,9 +120,10 @@ impl<'a> VMInterface for X86Interface<'a>{
 }
 
 impl<'a> X86Interface<'a> {
-    const X86_SPACE: u8 = 2;
-    const CODE_SECTION_SPACE: u8 = 1;
-    const DATA_SECTION_SPACE: u8 = 2;
+    /// The storage space which x86 contracts' code and data sections are kept in
+    pub const X86_SPACE: u8 = 2;
+    pub const CODE_SECTION_SPACE: u8 = 1;
+    pub const DATA_SECTION_SPACE: u8 = 2;
 
     /// Creates a new instance of the X86Interface
     pub fn new<'b>(cs: &'b mut dyn CallSystem, stack: &'b mut ContractCallStack) -> X86Interface<'b>{
@@ -135,6 +136,11 @@ impl<'a> X86Interface<'a> {
     }
     
     fn deploy(&mut self) -> Result<NeutronVMResult, NeutronError>{
+        //never overwrite the code of an existing contract
+        let code_key = vec![X86Interface::CODE_SECTION_SPACE, 0];
+        if self.call_system.state_key_exists(self.call_stack, X86Interface::X86_SPACE, &code_key)?{
+            return Err(Recoverable(RecoverableError::ContractAlreadyExists));
+        }
         let mut vm = VM::default();
         if self.init_cpu(&mut vm).is_err(){
             return Err(Unrecoverable(UnrecoverableError::ErrorInitializingVM));
@@ -362,6 +368,13 @@ impl <'a> Hypervisor for X86Interface<'a> {
             let feature = vm.reg32(Reg32::EAX);
             let function = vm.reg32(Reg32::ECX);
             let result = self.call_system.system_call(self.call_stack, feature, function);
+            let gas = self.call_stack.take_pending_gas();
+            if gas > vm.gas_remaining{
+                vm.gas_remaining = 0;
+                self.call_system.log_warning("System call exceeded the remaining gas");
+                return Err(VMError::SyscallError);
+            }
+            vm.gas_remaining -= gas;
             //how to handle unrecoverable??
             match result{
                 Err(e) => {
@@ -415,6 +428,9 @@ mod tests {
         fn write_state_key(&mut self, _stack: &mut ContractCallStack, _space: u8, _key: &[u8], _value: &[u8]) -> Result<(), NeutronError>{
             Err(Unrecoverable(UnrecoverableError::NotImplemented))
         }
+        fn state_key_exists(&mut self, _stack: &mut ContractCallStack, _space: u8, _key: &[u8]) -> Result<bool, NeutronError>{
+            Err(Unrecoverable(UnrecoverableError::NotImplemented))
+        }
     }
     #[test]
     fn test_x86_sccs_push(){
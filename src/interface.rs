@@ -1,8 +1,10 @@
 extern crate struct_deser;
 use struct_deser_derive::*;
+use serde_derive::Serialize;
 use crate::addressing::*;
 use crate::callstack::*;
 use crate::neutronerror::*;
+use crate::logsinks::LogChannel;
 
 
 /// The result of a smart contract execution
@@ -73,6 +75,33 @@ pub struct TransactionContext{
     pub total_fees: u64
 }
 
+impl TransactionContext{
+    /// Adds a spent UTXO to the transaction
+    pub fn add_input(&mut self, sender: NeutronAddress, value: u64, state: &[u8]) -> &mut TransactionContext{
+        self.inputs.push(TxItem{
+            sender: sender,
+            value: value,
+            state: state.to_vec()
+        });
+        self
+    }
+    /// Adds a created UTXO or other output to the transaction
+    pub fn add_output(&mut self, sender: NeutronAddress, value: u64, state: &[u8]) -> &mut TransactionContext{
+        self.outputs.push(TxItem{
+            sender: sender,
+            value: value,
+            state: state.to_vec()
+        });
+        self
+    }
+    /// Sets the fee totals of the transaction. total_fees should include total_gas_fees
+    pub fn set_fees(&mut self, total_gas_fees: u64, total_fees: u64) -> &mut TransactionContext{
+        self.total_gas_fees = total_gas_fees;
+        self.total_fees = total_fees;
+        self
+    }
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub struct TxItem{
 	/// The owner of this UTXO (or spent UTXO)
@@ -83,6 +112,40 @@ pub struct TxItem{
     pub state: Vec<u8>
 }
 
+/// A consensus visible event emitted by a smart contract
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+pub struct NeutronEvent{
+	/// The address of the smart contract which emitted the event
+	pub address: NeutronAddress,
+	/// The indexed topics of the event, in the order they were popped from the SCCS
+	pub topics: Vec<Vec<u8>>,
+	/// The non-indexed data of the event
+	pub data: Vec<u8>
+}
+
+/// The severity of a diagnostic log message
+#[derive(Clone, Copy, Debug, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, FromPrimitive)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel{
+    Debug = 0,
+    Info,
+    Warning,
+    Error
+}
+
+/// A log message emitted by a smart contract through the Logging feature
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogRecord{
+    pub level: LogLevel,
+    pub message: String,
+    /// The address of the smart contract which emitted the message
+    pub address: NeutronAddress,
+    /// The nest level of the context which emitted the message. The top level context is 0
+    pub nest_level: u32,
+    /// Key/value pairs attached to a structured log message
+    pub fields: Vec<(String, String)>
+}
+
 #[derive(Clone, Debug, Eq, PartialEq, Default)]
 pub struct BlockContext{
 	/// The creator of the current block
@@ -127,6 +190,50 @@ pub struct NeutronVersion{
 
 
 
+/// The gas costs charged by a CallSystem for system calls, on top of the gas consumed by the VM itself
+/// Costs marked as per word are charged for each started 32 byte word of input
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct GasSchedule{
+    pub sha256_base: u64,
+    pub sha256_per_word: u64,
+    pub double_sha256_base: u64,
+    pub double_sha256_per_word: u64,
+    pub ripemd160_base: u64,
+    pub ripemd160_per_word: u64,
+    pub secp256k1_verify: u64,
+    pub secp256k1_recover: u64
+}
+
+impl Default for GasSchedule{
+    fn default() -> GasSchedule{
+        GasSchedule{
+            sha256_base: 60,
+            sha256_per_word: 12,
+            double_sha256_base: 120,
+            double_sha256_per_word: 12,
+            ripemd160_base: 600,
+            ripemd160_per_word: 120,
+            secp256k1_verify: 3000,
+            secp256k1_recover: 3000
+        }
+    }
+}
+
+impl GasSchedule{
+    fn words(size: usize) -> u64{
+        ((size as u64) + 31) / 32
+    }
+    pub fn sha256_cost(&self, size: usize) -> u64{
+        self.sha256_base + self.sha256_per_word * GasSchedule::words(size)
+    }
+    pub fn double_sha256_cost(&self, size: usize) -> u64{
+        self.double_sha256_base + self.double_sha256_per_word * GasSchedule::words(size)
+    }
+    pub fn ripemd160_cost(&self, size: usize) -> u64{
+        self.ripemd160_base + self.ripemd160_per_word * GasSchedule::words(size)
+    }
+}
+
 pub trait VMInterface{
     fn execute(&mut self) -> Result<NeutronVMResult, NeutronError>;
 }
@@ -143,18 +250,31 @@ pub trait CallSystem{
     /// Write a state key to the database using the permanent storage feature set
     /// Used for writing bytecode etc by VMs
     fn write_state_key(&mut self, stack: &mut ContractCallStack, space: u8, key: &[u8], value: &[u8]) -> Result<(), NeutronError>;
-
-    fn log_error(&self, msg: &str){
-        println!("Error: {}", msg);
+    /// Check whether a state key exists in the database, regardless of whether its rent has expired
+    /// Used for checking that a contract's bytecode is not overwritten by VMs
+    fn state_key_exists(&mut self, stack: &mut ContractCallStack, space: u8, key: &[u8]) -> Result<bool, NeutronError>;
+
+    /// Receives every diagnostic message, both from smart contracts and from the hypervisor.
+    /// Only for diagnostic purposes, has no consensus effect. The default implementation prints to stdout
+    fn log(&mut self, _channel: LogChannel, level: LogLevel, msg: &str){
+        match level{
+            LogLevel::Error => println!("Error: {}", msg),
+            LogLevel::Warning => println!("Warning: {}", msg),
+            LogLevel::Info => println!("Info: {}", msg),
+            LogLevel::Debug => println!("Debug: {}", msg)
+        }
+    }
+    fn log_error(&mut self, msg: &str){
+        self.log(LogChannel::Hypervisor, LogLevel::Error, msg);
     }
-    fn log_warning(&self, msg: &str){
-        println!("Warning: {}", msg);
+    fn log_warning(&mut self, msg: &str){
+        self.log(LogChannel::Hypervisor, LogLevel::Warning, msg);
     }
-    fn log_info(&self, msg: &str){
-        println!("Info: {}", msg);
+    fn log_info(&mut self, msg: &str){
+        self.log(LogChannel::Hypervisor, LogLevel::Info, msg);
     }
-    fn log_debug(&self, msg: &str){
-        println!("Debug: {}", msg);
+    fn log_debug(&mut self, msg: &str){
+        self.log(LogChannel::Hypervisor, LogLevel::Debug, msg);
     }
 }
 
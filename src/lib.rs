@@ -1,11 +1,18 @@
 pub mod hypervisor;
 pub mod interface;
 pub mod db;
+pub mod filedb;
 pub mod addressing;
 pub mod syscall_interfaces;
 pub mod testbench;
 pub mod callstack;
 pub mod neutronerror;
+pub mod crypto;
+pub mod logsinks;
+pub mod merkle;
+pub mod snapshot;
+pub mod encoding;
+pub mod genesis;
 
 extern crate num;
 #[macro_use]
@@ -0,0 +1,239 @@
+extern crate log;
+extern crate serde_json;
+use crate::interface::*;
+use std::cell::RefCell;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use serde_derive::Serialize;
+
+/// The source of a diagnostic log message
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogChannel{
+    /// Messages emitted by smart contracts using the Logging feature
+    Contract,
+    /// Diagnostics emitted by VMs and the CallSystem itself
+    Hypervisor
+}
+
+/// A single diagnostic log message as given to a LogSink
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct LogEntry{
+    pub channel: LogChannel,
+    pub level: LogLevel,
+    pub message: String
+}
+
+/// A destination for diagnostic log messages. Logging has no consensus effect, so sinks should ignore their own failures
+pub trait LogSink{
+    fn log(&mut self, entry: &LogEntry);
+}
+
+/// Prints messages to stdout, prefixed with their level
+#[derive(Default)]
+pub struct StdoutSink{}
+
+impl LogSink for StdoutSink{
+    fn log(&mut self, entry: &LogEntry){
+        println!("{}: {}", level_name(entry.level), entry.message);
+    }
+}
+
+/// Keeps messages in memory. Clones of a MemorySink share the same entries,
+/// so one clone can be given to a LogSinks while another is kept for inspecting what was logged
+#[derive(Default, Clone)]
+pub struct MemorySink{
+    entries: Rc<RefCell<Vec<LogEntry>>>
+}
+
+impl MemorySink{
+    /// Copies out all entries logged so far
+    pub fn entries(&self) -> Vec<LogEntry>{
+        self.entries.borrow().clone()
+    }
+    pub fn clear(&self){
+        self.entries.borrow_mut().clear();
+    }
+}
+
+impl LogSink for MemorySink{
+    fn log(&mut self, entry: &LogEntry){
+        self.entries.borrow_mut().push(entry.clone());
+    }
+}
+
+/// Appends messages to a plain text file, one line per message
+pub struct FileSink{
+    file: File
+}
+
+impl FileSink{
+    /// Opens the file for appending, creating it if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<FileSink>{
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(FileSink{
+            file: file
+        })
+    }
+}
+
+impl LogSink for FileSink{
+    fn log(&mut self, entry: &LogEntry){
+        let channel = match entry.channel{
+            LogChannel::Contract => "contract",
+            LogChannel::Hypervisor => "hypervisor"
+        };
+        let _ = writeln!(self.file, "[{}] {}: {}", channel, level_name(entry.level), entry.message);
+    }
+}
+
+/// Writes each message as a single line JSON object with channel, level and message fields
+pub struct JsonLinesSink<W: Write>{
+    writer: W
+}
+
+impl<W: Write> JsonLinesSink<W>{
+    pub fn new(writer: W) -> JsonLinesSink<W>{
+        JsonLinesSink{
+            writer: writer
+        }
+    }
+    pub fn get_ref(&self) -> &W{
+        &self.writer
+    }
+    pub fn into_inner(self) -> W{
+        self.writer
+    }
+}
+
+impl JsonLinesSink<File>{
+    /// Opens the file for appending, creating it if it does not exist
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<JsonLinesSink<File>>{
+        Ok(JsonLinesSink::new(OpenOptions::new().create(true).append(true).open(path)?))
+    }
+}
+
+impl<W: Write> LogSink for JsonLinesSink<W>{
+    fn log(&mut self, entry: &LogEntry){
+        if serde_json::to_writer(&mut self.writer, entry).is_ok(){
+            let _ = self.writer.write_all(b"\n");
+        }
+    }
+}
+
+/// Forwards messages to the `log` crate facade.
+/// Contract messages use the target "neutron::contract" and hypervisor messages use "neutron::hypervisor"
+#[derive(Default)]
+pub struct LogCrateSink{}
+
+impl LogSink for LogCrateSink{
+    fn log(&mut self, entry: &LogEntry){
+        let target = match entry.channel{
+            LogChannel::Contract => "neutron::contract",
+            LogChannel::Hypervisor => "neutron::hypervisor"
+        };
+        let level = match entry.level{
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warning => log::Level::Warn,
+            LogLevel::Error => log::Level::Error
+        };
+        log::log!(target: target, level, "{}", entry.message);
+    }
+}
+
+fn level_name(level: LogLevel) -> &'static str{
+    match level{
+        LogLevel::Debug => "Debug",
+        LogLevel::Info => "Info",
+        LogLevel::Warning => "Warning",
+        LogLevel::Error => "Error"
+    }
+}
+
+struct RegisteredSink{
+    sink: Box<dyn LogSink>,
+    min_level: LogLevel,
+    channel: Option<LogChannel>
+}
+
+/// A set of sinks which messages are routed to, each with its own minimum level and channel filter
+#[derive(Default)]
+pub struct LogSinks{
+    sinks: Vec<RegisteredSink>
+}
+
+impl LogSinks{
+    /// Creates a set with a single StdoutSink receiving every message
+    pub fn stdout() -> LogSinks{
+        let mut sinks = LogSinks::default();
+        sinks.add(Box::new(StdoutSink{}), LogLevel::Debug, None);
+        sinks
+    }
+    /// Adds a sink which receives messages of at least min_level.
+    /// If channel is given, the sink only receives messages from that channel
+    pub fn add(&mut self, sink: Box<dyn LogSink>, min_level: LogLevel, channel: Option<LogChannel>){
+        self.sinks.push(RegisteredSink{
+            sink: sink,
+            min_level: min_level,
+            channel: channel
+        });
+    }
+    /// Removes all sinks, discarding any further messages
+    pub fn clear(&mut self){
+        self.sinks.clear();
+    }
+    pub fn log(&mut self, channel: LogChannel, level: LogLevel, message: &str){
+        let entry = LogEntry{
+            channel: channel,
+            level: level,
+            message: message.to_string()
+        };
+        for s in self.sinks.iter_mut(){
+            if level < s.min_level{
+                continue;
+            }
+            if s.channel.is_some() && s.channel != Some(channel){
+                continue;
+            }
+            s.sink.log(&entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_sink_filtering(){
+        let memory = MemorySink::default();
+        let contract_only = MemorySink::default();
+        let mut sinks = LogSinks::default();
+        sinks.add(Box::new(memory.clone()), LogLevel::Info, None);
+        sinks.add(Box::new(contract_only.clone()), LogLevel::Debug, Some(LogChannel::Contract));
+        sinks.log(LogChannel::Hypervisor, LogLevel::Debug, "ignored");
+        sinks.log(LogChannel::Hypervisor, LogLevel::Warning, "diagnostic");
+        sinks.log(LogChannel::Contract, LogLevel::Debug, "contract debug");
+        assert_eq!(memory.entries(), vec![LogEntry{
+            channel: LogChannel::Hypervisor,
+            level: LogLevel::Warning,
+            message: "diagnostic".to_string()
+        }]);
+        assert_eq!(contract_only.entries().len(), 1);
+        assert_eq!(contract_only.entries()[0].message, "contract debug");
+    }
+    #[test]
+    fn test_json_lines(){
+        let mut sink = JsonLinesSink::new(vec![]);
+        sink.log(&LogEntry{
+            channel: LogChannel::Contract,
+            level: LogLevel::Info,
+            message: "say \"hi\"".to_string()
+        });
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        assert_eq!(output, "{\"channel\":\"contract\",\"level\":\"info\",\"message\":\"say \\\"hi\\\"\"}\n");
+    }
+}
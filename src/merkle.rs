@@ -0,0 +1,318 @@
+extern crate neutron_star_constants;
+use std::collections::HashMap;
+use neutron_star_constants::*;
+use crate::crypto::sha256;
+
+/*
+## State root
+
+The state root commits to every (short address, key, value) triple in the database using a sorted binary Merkle tree.
+
+* The leaf key of a triple is the little endian u32 address version, followed by the 20 byte address data, followed by the key
+* Leaves are sorted by leaf key, compared bytewise
+* A leaf hash is SHA256(0x00 || leaf key length as little endian u32 || leaf key || SHA256(value))
+* An inner node hash is SHA256(0x01 || left || right)
+* When a level has an odd number of nodes, the last node is carried up to the next level unchanged
+* The root of an empty tree is 32 zero bytes
+
+Because leaves are sorted, a key which does not exist can be proven absent by proving the inclusion of the two adjacent leaves
+which the key would be sorted between (or only one of them, when the key would be first or last in the tree).
+*/
+
+/// The state root of a database which holds no state
+pub const EMPTY_STATE_ROOT: [u8; 32] = [0; 32];
+
+/// Builds the leaf key which identifies a state key of an address within the state tree
+pub fn state_leaf_key(address: &NeutronShortAddress, key: &[u8]) -> Vec<u8>{
+    let mut leaf = address.version.to_le_bytes().to_vec();
+    leaf.extend_from_slice(&address.data);
+    leaf.extend_from_slice(key);
+    leaf
+}
+
+/// Computes the hash of a single leaf of the state tree
+pub fn leaf_hash(leaf_key: &[u8], value: &[u8]) -> [u8; 32]{
+    let mut data = vec![0];
+    data.extend_from_slice(&(leaf_key.len() as u32).to_le_bytes());
+    data.extend_from_slice(leaf_key);
+    data.extend_from_slice(&sha256(value));
+    sha256(&data)
+}
+
+/// Computes the hash of an inner node of the state tree
+pub fn node_hash(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32]{
+    let mut data = vec![1];
+    data.extend_from_slice(left);
+    data.extend_from_slice(right);
+    sha256(&data)
+}
+
+/// Computes the next level of the tree from the nodes of a level
+pub(crate) fn next_level(level: &[[u8; 32]]) -> Vec<[u8; 32]>{
+    level.chunks(2).map(|pair| {
+        if pair.len() == 2{
+            node_hash(&pair[0], &pair[1])
+        }else{
+            pair[0]
+        }
+    }).collect()
+}
+
+/// Computes the Merkle root of leaf hashes which are already in sorted order
+pub fn merkle_root(leaves: &[[u8; 32]]) -> [u8; 32]{
+    if leaves.len() == 0{
+        return EMPTY_STATE_ROOT;
+    }
+    let mut level = leaves.to_vec();
+    while level.len() > 1{
+        level = next_level(&level);
+    }
+    level[0]
+}
+
+/// Collects every triple in the storage as (leaf key, value) pairs sorted by leaf key
+pub fn sorted_leaves(storage: &HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>) -> Vec<(Vec<u8>, Vec<u8>)>{
+    let mut leaves = vec![];
+    for (address, kv) in storage.iter(){
+        for (key, value) in kv.iter(){
+            leaves.push((state_leaf_key(address, key), value.clone()));
+        }
+    }
+    leaves.sort();
+    leaves
+}
+
+/// Computes the state root over all triples in the storage
+pub fn compute_state_root(storage: &HashMap<NeutronShortAddress, HashMap<Vec<u8>, Vec<u8>>>) -> [u8; 32]{
+    let hashes: Vec<[u8; 32]> = sorted_leaves(storage).iter().map(|(k, v)| leaf_hash(k, v)).collect();
+    merkle_root(&hashes)
+}
+
+/// Proves that a single leaf is part of a tree with a known number of leaves
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LeafProof{
+    pub leaf_key: Vec<u8>,
+    pub value: Vec<u8>,
+    /// The position of the leaf within the sorted leaves
+    pub index: u64,
+    /// The sibling hashes from the bottom of the tree upward. Levels where the node is carried up have no sibling
+    pub siblings: Vec<[u8; 32]>
+}
+
+/// Proves either the value of a state key, or that a state key does not exist
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateProof{
+    Inclusion{
+        leaf_count: u64,
+        leaf: LeafProof
+    },
+    Exclusion{
+        leaf_count: u64,
+        /// The leaf sorted immediately before the key, if any
+        before: Option<LeafProof>,
+        /// The leaf sorted immediately after the key, if any
+        after: Option<LeafProof>
+    }
+}
+
+/// The error returned when a proof does not prove anything about the given key and state root
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidProof;
+
+fn build_leaf_proof(levels: &[Vec<[u8; 32]>], leaves: &[(Vec<u8>, Vec<u8>)], index: usize) -> LeafProof{
+    let mut siblings = vec![];
+    let mut i = index;
+    for level in levels.iter(){
+        if level.len() == 1{
+            break;
+        }
+        let sibling = i ^ 1;
+        if sibling < level.len(){
+            siblings.push(level[sibling]);
+        }
+        i /= 2;
+    }
+    LeafProof{
+        leaf_key: leaves[index].0.clone(),
+        value: leaves[index].1.clone(),
+        index: index as u64,
+        siblings: siblings
+    }
+}
+
+/// Generates a proof for a leaf key using (leaf key, value) pairs which are sorted by leaf key, such as from sorted_leaves
+pub fn generate_state_proof(leaves: &[(Vec<u8>, Vec<u8>)], leaf_key: &[u8]) -> StateProof{
+    let mut levels = vec![leaves.iter().map(|(k, v)| leaf_hash(k, v)).collect::<Vec<[u8; 32]>>()];
+    while levels.last().unwrap().len() > 1{
+        let next = next_level(levels.last().unwrap());
+        levels.push(next);
+    }
+    let leaf_count = leaves.len() as u64;
+    match leaves.binary_search_by(|(k, _v)| k.as_slice().cmp(leaf_key)){
+        Ok(index) => {
+            StateProof::Inclusion{
+                leaf_count: leaf_count,
+                leaf: build_leaf_proof(&levels, leaves, index)
+            }
+        },
+        Err(index) => {
+            StateProof::Exclusion{
+                leaf_count: leaf_count,
+                before: if index > 0 { Some(build_leaf_proof(&levels, leaves, index - 1)) } else { None },
+                after: if index < leaves.len() { Some(build_leaf_proof(&levels, leaves, index)) } else { None }
+            }
+        }
+    }
+}
+
+/// Computes the root implied by a leaf proof, or None if the proof is malformed
+fn root_from_leaf(leaf: &LeafProof, leaf_count: u64) -> Option<[u8; 32]>{
+    if leaf.index >= leaf_count{
+        return None;
+    }
+    let mut hash = leaf_hash(&leaf.leaf_key, &leaf.value);
+    let mut index = leaf.index;
+    let mut size = leaf_count;
+    let mut siblings = leaf.siblings.iter();
+    while size > 1{
+        if index % 2 == 1{
+            hash = node_hash(siblings.next()?, &hash);
+        }else if index + 1 < size{
+            hash = node_hash(&hash, siblings.next()?);
+        }
+        index /= 2;
+        size = (size + 1) / 2;
+    }
+    if siblings.next().is_some(){
+        return None;
+    }
+    Some(hash)
+}
+
+/// Verifies a proof about a state key against a state root.
+/// Returns the value of the key if the proof shows it exists, or None if the proof shows it does not exist
+pub fn verify_state_proof(root: &[u8; 32], address: &NeutronShortAddress, key: &[u8], proof: &StateProof) -> Result<Option<Vec<u8>>, InvalidProof>{
+    let leaf_key = state_leaf_key(address, key);
+    match proof{
+        StateProof::Inclusion{leaf_count, leaf} => {
+            if leaf.leaf_key != leaf_key || root_from_leaf(leaf, *leaf_count) != Some(*root){
+                return Err(InvalidProof);
+            }
+            Ok(Some(leaf.value.clone()))
+        },
+        StateProof::Exclusion{leaf_count, before, after} => {
+            if *leaf_count == 0{
+                if before.is_some() || after.is_some() || *root != EMPTY_STATE_ROOT{
+                    return Err(InvalidProof);
+                }
+                return Ok(None);
+            }
+            if let Some(b) = before{
+                if b.leaf_key >= leaf_key || root_from_leaf(b, *leaf_count) != Some(*root){
+                    return Err(InvalidProof);
+                }
+            }
+            if let Some(a) = after{
+                if a.leaf_key <= leaf_key || root_from_leaf(a, *leaf_count) != Some(*root){
+                    return Err(InvalidProof);
+                }
+            }
+            let adjacent = match (before, after){
+                (Some(b), Some(a)) => a.index == b.index + 1,
+                (Some(b), None) => b.index == *leaf_count - 1,
+                (None, Some(a)) => a.index == 0,
+                (None, None) => false
+            };
+            if !adjacent{
+                return Err(InvalidProof);
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    fn address(n: u8) -> NeutronShortAddress{
+        let mut a = NeutronShortAddress::default();
+        a.version = 2;
+        a.data[0] = n;
+        a
+    }
+    #[test]
+    fn test_state_root(){
+        let mut storage = HashMap::new();
+        assert_eq!(compute_state_root(&storage), EMPTY_STATE_ROOT);
+        let mut kv = HashMap::new();
+        kv.insert(vec![1], vec![10]);
+        storage.insert(address(1), kv);
+        let single = compute_state_root(&storage);
+        assert_eq!(single, leaf_hash(&state_leaf_key(&address(1), &[1]), &[10]));
+
+        let mut kv = HashMap::new();
+        kv.insert(vec![1], vec![20]);
+        kv.insert(vec![2], vec![30]);
+        storage.insert(address(2), kv);
+        let root = compute_state_root(&storage);
+        let l: Vec<[u8; 32]> = vec![
+            leaf_hash(&state_leaf_key(&address(1), &[1]), &[10]),
+            leaf_hash(&state_leaf_key(&address(2), &[1]), &[20]),
+            leaf_hash(&state_leaf_key(&address(2), &[2]), &[30])
+        ];
+        assert_eq!(root, node_hash(&node_hash(&l[0], &l[1]), &l[2]));
+
+        storage.get_mut(&address(2)).unwrap().insert(vec![2], vec![31]);
+        assert_ne!(compute_state_root(&storage), root);
+    }
+    #[test]
+    fn test_state_proofs(){
+        for count in 0..8u8{
+            let mut storage = HashMap::new();
+            let mut kv = HashMap::new();
+            for i in 0..count{
+                kv.insert(vec![i * 2 + 1], vec![i]);
+            }
+            storage.insert(address(1), kv);
+            let root = compute_state_root(&storage);
+            let leaves = sorted_leaves(&storage);
+            for k in 0..(count * 2 + 2){
+                let proof = generate_state_proof(&leaves, &state_leaf_key(&address(1), &[k]));
+                let expected = if k % 2 == 1 && k < count * 2 { Some(vec![k / 2]) } else { None };
+                assert_eq!(verify_state_proof(&root, &address(1), &[k], &proof), Ok(expected.clone()), "count {} key {}", count, k);
+                //proofs can not be used for other roots, and inclusion proofs can not be used for other keys
+                if expected.is_some(){
+                    assert!(verify_state_proof(&root, &address(2), &[k], &proof).is_err());
+                }
+                assert!(verify_state_proof(&[1; 32], &address(1), &[k], &proof).is_err());
+            }
+        }
+    }
+    #[test]
+    fn test_invalid_proofs(){
+        let mut storage = HashMap::new();
+        let mut kv = HashMap::new();
+        for i in 0..5u8{
+            kv.insert(vec![i * 2], vec![i]);
+        }
+        storage.insert(address(1), kv);
+        let root = compute_state_root(&storage);
+        let leaves = sorted_leaves(&storage);
+        let mut proof = generate_state_proof(&leaves, &state_leaf_key(&address(1), &[2]));
+        if let StateProof::Inclusion{ref mut leaf, ..} = proof{
+            leaf.value = vec![9];
+        }
+        assert!(verify_state_proof(&root, &address(1), &[2], &proof).is_err());
+
+        //an exclusion proof which skips over an existing key
+        let first = generate_state_proof(&leaves, &state_leaf_key(&address(1), &[1]));
+        let second = generate_state_proof(&leaves, &state_leaf_key(&address(1), &[3]));
+        let forged = match (first, second){
+            (StateProof::Exclusion{leaf_count, before, ..}, StateProof::Exclusion{after, ..}) => {
+                StateProof::Exclusion{leaf_count: leaf_count, before: before, after: after}
+            },
+            _ => panic!("expected exclusion proofs")
+        };
+        assert!(verify_state_proof(&root, &address(1), &[1], &forged).is_err());
+    }
+}
\ No newline at end of file
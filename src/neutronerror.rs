@@ -11,7 +11,9 @@ pub enum UnrecoverableError{
     UnknownVM,
     DatabaseCommitError,
     DatabaseWritingError,
-    ErrorInitializingVM
+    ErrorInitializingVM,
+    /// State was read as of a past block height but history is not being kept
+    HistoryNotEnabled
 
 }
 
@@ -27,7 +29,13 @@ pub enum RecoverableError{
     ContractSignaledError,
     ContractExecutionError,
     InvalidHypervisorInterrupt,
-    StackItemTooSmall
+    StackItemTooSmall,
+    IndexOutOfRange,
+    InvalidSignature,
+    InvalidTypeTag,
+    StateItemTooLarge,
+    InvalidAddress,
+    ContractAlreadyExists
 
 }
 
@@ -0,0 +1,309 @@
+extern crate neutron_star_constants;
+extern crate rmp_serde;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde_derive::{Serialize, Deserialize};
+use neutron_star_constants::*;
+use crate::db::*;
+
+/// The version of the snapshot format written by this crate. Snapshots of any other version are rejected when loading.
+/// Version 2 stores 20 byte addresses under their own data rather than a hash of it
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 2;
+
+#[derive(Debug)]
+pub enum SnapshotError{
+    Io(io::Error),
+    /// The snapshot could not be encoded or decoded as MessagePack
+    Encoding(String),
+    /// The snapshot was written using a different format version
+    UnsupportedVersion(u32),
+    /// The state restored from the snapshot does not match the state root recorded within it
+    StateRootMismatch,
+    /// The database refused the restored state
+    Database,
+    /// The database being restored into already contains committed state
+    DatabaseNotEmpty,
+    /// An entry's address does not contain exactly 20 bytes of data
+    InvalidAddress
+}
+
+impl fmt::Display for SnapshotError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            SnapshotError::Io(e) => {
+                write!(f, "Snapshot I/O error: {}", e)
+            },
+            SnapshotError::Encoding(e) => {
+                write!(f, "Snapshot encoding error: {}", e)
+            },
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported snapshot format version {} (expected {})", v, SNAPSHOT_FORMAT_VERSION)
+            },
+            SnapshotError::StateRootMismatch => {
+                write!(f, "Snapshot state does not match its state root")
+            },
+            SnapshotError::Database => {
+                write!(f, "Error restoring snapshot into the database")
+            },
+            SnapshotError::DatabaseNotEmpty => {
+                write!(f, "Snapshots can only be restored into an empty database")
+            },
+            SnapshotError::InvalidAddress => {
+                write!(f, "Snapshot entry has an invalid address")
+            }
+        }
+    }
+}
+
+impl error::Error for SnapshotError{
+}
+
+impl From<io::Error> for SnapshotError{
+    fn from(e: io::Error) -> SnapshotError{
+        SnapshotError::Io(e)
+    }
+}
+
+/// A single key of committed state. Either the value or the rent may be missing
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotEntry{
+    pub address_version: u32,
+    pub address_data: Vec<u8>,
+    pub key: Vec<u8>,
+    pub value: Option<Vec<u8>>,
+    pub rent: Option<u32>
+}
+
+impl SnapshotEntry{
+    pub fn address(&self) -> Result<NeutronShortAddress, SnapshotError>{
+        let mut address = NeutronShortAddress::default();
+        if self.address_data.len() != address.data.len(){
+            return Err(SnapshotError::InvalidAddress);
+        }
+        address.version = self.address_version;
+        address.data.copy_from_slice(&self.address_data);
+        Ok(address)
+    }
+}
+
+/// The entire committed state of a database, including the code of all deployed contracts, which is kept in its own storage space.
+/// Uncommitted checkpoints and state history are not included
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DBSnapshot{
+    pub format_version: u32,
+    /// The block height which the state was last committed at
+    pub height: u32,
+    pub state_root: [u8; 32],
+    /// Ordered by address and then key, so that the same state always results in the same snapshot
+    pub entries: Vec<SnapshotEntry>
+}
+
+/// Only used to check the format version before decoding the rest of a snapshot
+#[derive(Deserialize)]
+struct SnapshotHeader{
+    format_version: u32
+}
+
+impl DBSnapshot{
+    /// Encodes the snapshot as MessagePack
+    pub fn to_bytes(&self) -> Result<Vec<u8>, SnapshotError>{
+        match rmp_serde::to_vec_named(self){
+            Err(e) => {
+                Err(SnapshotError::Encoding(e.to_string()))
+            },
+            Ok(v) => {
+                Ok(v)
+            }
+        }
+    }
+    /// Decodes a MessagePack snapshot, checking that it uses the current format version
+    pub fn from_bytes(bytes: &[u8]) -> Result<DBSnapshot, SnapshotError>{
+        let header: SnapshotHeader = match rmp_serde::from_read(bytes){
+            Err(e) => {
+                return Err(SnapshotError::Encoding(e.to_string()));
+            },
+            Ok(h) => {
+                h
+            }
+        };
+        if header.format_version != SNAPSHOT_FORMAT_VERSION{
+            return Err(SnapshotError::UnsupportedVersion(header.format_version));
+        }
+        match rmp_serde::from_read(bytes){
+            Err(e) => {
+                Err(SnapshotError::Encoding(e.to_string()))
+            },
+            Ok(s) => {
+                Ok(s)
+            }
+        }
+    }
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError>{
+        fs::write(path, self.to_bytes()?)?;
+        Ok(())
+    }
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<DBSnapshot, SnapshotError>{
+        DBSnapshot::from_bytes(&fs::read(path)?)
+    }
+    /// Writes all of the snapshot's state into an empty database as a single commit at the snapshot's block height.
+    /// The state root is checked before the database is modified, so a snapshot which fails to restore leaves it unchanged.
+    /// Any uncommitted checkpoints of the database are discarded
+    pub fn restore(&self, db: &mut dyn NeutronDB) -> Result<(), SnapshotError>{
+        if !db.snapshot().entries.is_empty(){
+            return Err(SnapshotError::DatabaseNotEmpty);
+        }
+        ProtoDB::from_snapshot(self)?;
+        db.clear_checkpoints();
+        self.write_into(db)
+    }
+    fn write_into(&self, db: &mut dyn NeutronDB) -> Result<(), SnapshotError>{
+        if db.checkpoint().is_err(){
+            return Err(SnapshotError::Database);
+        }
+        for entry in self.entries.iter(){
+            let address = match entry.address(){
+                Err(e) => {
+                    db.clear_checkpoints();
+                    return Err(e);
+                },
+                Ok(a) => {
+                    a
+                }
+            };
+            if let Some(value) = entry.value.as_ref(){
+                if db.write_key(&address, &entry.key, value).is_err(){
+                    db.clear_checkpoints();
+                    return Err(SnapshotError::Database);
+                }
+            }
+            if let Some(rent) = entry.rent{
+                if db.write_rent(&address, &entry.key, rent).is_err(){
+                    db.clear_checkpoints();
+                    return Err(SnapshotError::Database);
+                }
+            }
+        }
+        db.set_block_height(self.height);
+        if db.commit().is_err(){
+            db.clear_checkpoints();
+            return Err(SnapshotError::Database);
+        }
+        Ok(())
+    }
+}
+
+impl ProtoDB{
+    /// Creates a new database containing the state of a snapshot
+    pub fn from_snapshot(snapshot: &DBSnapshot) -> Result<ProtoDB, SnapshotError>{
+        let mut db = ProtoDB::default();
+        snapshot.write_into(&mut db)?;
+        if db.state_root() != snapshot.state_root{
+            return Err(SnapshotError::StateRootMismatch);
+        }
+        Ok(db)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[test]
+    fn test_snapshot_roundtrip(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        a.data[5] = 20;
+        let mut db = ProtoDB::default();
+        db.set_block_height(7);
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[1], &[8, 8]).is_ok());
+        assert!(db.write_key(&a, &[2], &[9]).is_ok());
+        assert!(db.write_rent(&a, &[1], 50).is_ok());
+        assert!(db.commit().is_ok());
+        //uncommitted state is not included
+        assert!(db.checkpoint().is_ok());
+        assert!(db.write_key(&a, &[3], &[10]).is_ok());
+
+        let snapshot = db.snapshot();
+        assert_eq!(snapshot.height, 7);
+        let bytes = snapshot.to_bytes().unwrap();
+        let decoded = DBSnapshot::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, snapshot);
+        let mut restored = ProtoDB::from_snapshot(&decoded).unwrap();
+        assert_eq!(restored.state_root(), db.state_root());
+        assert_eq!(restored.read_key(&a, &[1]).unwrap(), vec![8, 8]);
+        assert_eq!(restored.read_rent(&a, &[1]).unwrap(), Some(50));
+        assert_eq!(restored.read_rent(&a, &[2]).unwrap(), None);
+        assert!(restored.read_key(&a, &[3]).is_err());
+        assert_eq!(restored.snapshot().to_bytes().unwrap(), bytes);
+    }
+    #[test]
+    fn test_snapshot_validation(){
+        let mut snapshot = DBSnapshot::default();
+        snapshot.format_version = SNAPSHOT_FORMAT_VERSION + 1;
+        match DBSnapshot::from_bytes(&snapshot.to_bytes().unwrap()){
+            Err(SnapshotError::UnsupportedVersion(v)) => {
+                assert_eq!(v, SNAPSHOT_FORMAT_VERSION + 1);
+            },
+            _ => {
+                panic!("expected an unsupported version error");
+            }
+        }
+        assert!(DBSnapshot::from_bytes(&[1, 2, 3]).is_err());
+
+        snapshot.format_version = SNAPSHOT_FORMAT_VERSION;
+        snapshot.entries.push(SnapshotEntry{
+            address_version: 100,
+            address_data: vec![1; 20],
+            key: vec![1],
+            value: Some(vec![2]),
+            rent: None
+        });
+        match ProtoDB::from_snapshot(&snapshot){
+            Err(SnapshotError::StateRootMismatch) => {},
+            _ => {
+                panic!("expected a state root mismatch");
+            }
+        }
+        snapshot.entries[0].address_data = vec![1; 19];
+        match ProtoDB::from_snapshot(&snapshot){
+            Err(SnapshotError::InvalidAddress) => {},
+            _ => {
+                panic!("expected an invalid address");
+            }
+        }
+    }
+    #[test]
+    fn test_restore_leaves_database_unchanged(){
+        let mut a = NeutronShortAddress::default();
+        a.version=100;
+        let mut source = ProtoDB::default();
+        assert!(source.checkpoint().is_ok());
+        assert!(source.write_key(&a, &[1], &[2]).is_ok());
+        assert!(source.commit().is_ok());
+        let mut snapshot = source.snapshot();
+
+        let mut db = ProtoDB::default();
+        snapshot.state_root = [0; 32];
+        match snapshot.restore(&mut db){
+            Err(SnapshotError::StateRootMismatch) => {},
+            _ => {
+                panic!("expected a state root mismatch");
+            }
+        }
+        assert!(db.snapshot().entries.is_empty());
+        assert!(db.read_key(&a, &[1]).is_err());
+
+        snapshot.state_root = source.state_root();
+        assert!(snapshot.restore(&mut db).is_ok());
+        assert_eq!(db.read_key(&a, &[1]).unwrap(), vec![2]);
+        match snapshot.restore(&mut db){
+            Err(SnapshotError::DatabaseNotEmpty) => {},
+            _ => {
+                panic!("expected restoring into a database with state to fail");
+            }
+        }
+    }
+}
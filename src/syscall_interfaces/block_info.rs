@@ -0,0 +1,96 @@
+use crate::callstack::*;
+use crate::neutronerror::*;
+use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
+/*
+## Block Information
+
+ID: 3
+
+Functions:
+
+* available() -> (version: u32)
+* block_creator() -> (address)
+* block_gas_limit() -> (u64)
+* block_difficulty() -> (u64)
+* block_height() -> (u32)
+* previous_block_time() -> (u64)
+* block_hash(number: u32) -> (hash: [u8; 32])
+
+All integers are pushed to the SCCS as little endian values of their exact size.
+Addresses are pushed as a little endian u32 version followed by the address data.
+
+block_hash uses the same numbering as BlockContext::previous_hashes, ie, 0 is the block just before the current one.
+Requesting a block hash which is not available will result in an IndexOutOfRange error
+*/
+
+pub const BLOCK_INFO_FEATURE: u32 = 3;
+pub const BLOCK_INFO_FEATURE_VERSION: u32 = 1;
+
+#[derive(FromPrimitive)]
+pub enum BlockInfoFunctions{
+    Available = 0,
+    BlockCreator = 1,
+    BlockGasLimit,
+    BlockDifficulty,
+    BlockHeight,
+    PreviousBlockTime,
+    BlockHash
+}
+
+pub trait BlockInfo{
+    fn try_syscall(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<bool, NeutronError>{
+        if feature != BLOCK_INFO_FEATURE{
+            return Ok(false);
+        }
+        let f = num::FromPrimitive::from_u32(function);
+        if f.is_none(){
+            return Err(Recoverable(RecoverableError::InvalidSystemFunction));
+        }
+        let f=f.unwrap();
+        let result = match f{
+            BlockInfoFunctions::BlockCreator => {
+                self.block_creator(stack)
+            },
+            BlockInfoFunctions::BlockGasLimit => {
+                self.block_gas_limit(stack)
+            },
+            BlockInfoFunctions::BlockDifficulty => {
+                self.block_difficulty(stack)
+            },
+            BlockInfoFunctions::BlockHeight => {
+                self.block_height(stack)
+            },
+            BlockInfoFunctions::PreviousBlockTime => {
+                self.previous_block_time(stack)
+            },
+            BlockInfoFunctions::BlockHash => {
+                self.block_hash(stack)
+            },
+            BlockInfoFunctions::Available => {
+                stack.push_sccs_u32(BLOCK_INFO_FEATURE_VERSION)
+            }
+        };
+        if result.is_err(){
+            Err(result.unwrap_err())
+        }else{
+            Ok(true)
+        }
+    }
+    fn block_creator(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn block_gas_limit(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn block_difficulty(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn block_height(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn previous_block_time(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn block_hash(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+}
+
+/// Handler for registering the Block Information feature of a CallSystem with a SyscallDispatcher
+pub struct BlockInfoFeature;
+
+impl<T: BlockInfo> FeatureHandler<T> for BlockInfoFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        BlockInfo::try_syscall(host, stack, BLOCK_INFO_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
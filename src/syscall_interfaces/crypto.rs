@@ -0,0 +1,95 @@
+use crate::callstack::*;
+use crate::neutronerror::*;
+use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
+/*
+## Cryptography
+
+ID: 6
+
+Functions:
+
+* available() -> (version: u32)
+* sha256(data) -> (hash: [u8; 32])
+* ripemd160(data) -> (hash: [u8; 20])
+* double_sha256(data) -> (hash: [u8; 32])
+* secp256k1_verify(hash: [u8; 32], signature: [u8; 64], public_key) -> (valid: u8)
+* secp256k1_recover(hash: [u8; 32], signature: [u8; 65]) -> (public_key: [u8; 33])
+
+Arguments are popped in the order listed, ie, the first argument should be the top item of the SCCS.
+
+secp256k1_verify expects a compact signature and a compressed or uncompressed serialized public key.
+It pushes 1 if the signature is valid and 0 for an invalid signature or malformed key/signature.
+Both low S and high S forms of a signature are accepted.
+
+secp256k1_recover expects a compact signature followed by a single byte recovery ID and pushes the compressed public key.
+If no public key can be recovered an InvalidSignature error is returned.
+
+Each function is charged gas according to the CallSystem's GasSchedule
+*/
+
+pub const CRYPTO_FEATURE: u32 = 6;
+pub const CRYPTO_FEATURE_VERSION: u32 = 1;
+
+#[derive(FromPrimitive)]
+pub enum CryptoFunctions{
+    Available = 0,
+    Sha256 = 1,
+    Ripemd160,
+    DoubleSha256,
+    Secp256k1Verify,
+    Secp256k1Recover
+}
+
+pub trait CryptoInterface{
+    fn try_syscall(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<bool, NeutronError>{
+        if feature != CRYPTO_FEATURE{
+            return Ok(false);
+        }
+        let f = num::FromPrimitive::from_u32(function);
+        if f.is_none(){
+            return Err(Recoverable(RecoverableError::InvalidSystemFunction));
+        }
+        let f=f.unwrap();
+        let result = match f{
+            CryptoFunctions::Sha256 => {
+                self.sha256(stack)
+            },
+            CryptoFunctions::Ripemd160 => {
+                self.ripemd160(stack)
+            },
+            CryptoFunctions::DoubleSha256 => {
+                self.double_sha256(stack)
+            },
+            CryptoFunctions::Secp256k1Verify => {
+                self.secp256k1_verify(stack)
+            },
+            CryptoFunctions::Secp256k1Recover => {
+                self.secp256k1_recover(stack)
+            },
+            CryptoFunctions::Available => {
+                stack.push_sccs_u32(CRYPTO_FEATURE_VERSION)
+            }
+        };
+        if result.is_err(){
+            Err(result.unwrap_err())
+        }else{
+            Ok(true)
+        }
+    }
+    fn sha256(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn ripemd160(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn double_sha256(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn secp256k1_verify(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn secp256k1_recover(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+}
+
+/// Handler for registering the Cryptography feature of a CallSystem with a SyscallDispatcher
+pub struct CryptoFeature;
+
+impl<T: CryptoInterface> FeatureHandler<T> for CryptoFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        CryptoInterface::try_syscall(host, stack, CRYPTO_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,79 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::callstack::*;
+use crate::neutronerror::*;
+use crate::neutronerror::NeutronError::*;
+
+/// Handles every function of a single system call feature on behalf of a CallSystem of type T
+/// Handlers should keep their state within T so that they can be shared and reentered by nested executions
+pub trait FeatureHandler<T>{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>;
+}
+
+impl<T, F> FeatureHandler<T> for F where F: Fn(&mut T, &mut ContractCallStack, u32) -> Result<u32, NeutronError>{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        self(host, stack, function)
+    }
+}
+
+/// Maps feature IDs to the handlers which implement them, so that a CallSystem can be composed of any set of features
+pub struct SyscallDispatcher<T>{
+    handlers: HashMap<u32, Rc<dyn FeatureHandler<T>>>
+}
+
+impl<T> Default for SyscallDispatcher<T>{
+    fn default() -> SyscallDispatcher<T>{
+        SyscallDispatcher{
+            handlers: HashMap::new()
+        }
+    }
+}
+
+impl<T> SyscallDispatcher<T>{
+    /// Registers a handler for a feature which has no handler yet. Returns false if the feature is already handled
+    pub fn register(&mut self, feature: u32, handler: Rc<dyn FeatureHandler<T>>) -> bool{
+        if self.handlers.contains_key(&feature){
+            return false;
+        }
+        self.handlers.insert(feature, handler);
+        true
+    }
+    /// Registers a handler for a feature, replacing and returning any existing handler
+    pub fn override_feature(&mut self, feature: u32, handler: Rc<dyn FeatureHandler<T>>) -> Option<Rc<dyn FeatureHandler<T>>>{
+        self.handlers.insert(feature, handler)
+    }
+    /// Removes the handler of a feature so that calls to it result in an InvalidSystemFeature error
+    pub fn disable(&mut self, feature: u32) -> Option<Rc<dyn FeatureHandler<T>>>{
+        self.handlers.remove(&feature)
+    }
+    /// Checks if a handler is registered for the feature
+    pub fn is_enabled(&self, feature: u32) -> bool{
+        self.handlers.contains_key(&feature)
+    }
+    /// Gets the handler of a feature.
+    /// The handler is reference counted so that it can be called when the dispatcher is owned by the host itself
+    pub fn handler(&self, feature: u32) -> Option<Rc<dyn FeatureHandler<T>>>{
+        self.handlers.get(&feature).cloned()
+    }
+    /// Calls the handler of a feature, or returns an InvalidSystemFeature error if there is no handler
+    pub fn dispatch(&self, host: &mut T, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<u32, NeutronError>{
+        SyscallDispatcher::call_handler(self.handler(feature), host, stack, function)
+    }
+    /// The same as dispatch, but for a host which owns its dispatcher, and so can not lend it out while also being borrowed mutably.
+    /// The dispatcher is looked up within the host, such as `|host| &host.syscalls`, and released before the handler is called
+    pub fn dispatch_owned<F>(dispatcher: F, host: &mut T, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<u32, NeutronError>
+        where F: Fn(&T) -> &SyscallDispatcher<T>{
+        let handler = dispatcher(host).handler(feature);
+        SyscallDispatcher::call_handler(handler, host, stack, function)
+    }
+    fn call_handler(handler: Option<Rc<dyn FeatureHandler<T>>>, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        match handler{
+            None => {
+                Err(Recoverable(RecoverableError::InvalidSystemFeature))
+            },
+            Some(h) => {
+                h.system_call(host, stack, function)
+            }
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use crate::callstack::*;
+use crate::neutronerror::*;
+use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
+/*
+## Events
+
+ID: 5
+
+Functions:
+
+* available() -> (version: u32)
+* emit_event(count, topic, ..., data)
+
+count is a single byte item specifying how many topics follow it on the stack, up to MAX_EVENT_TOPICS.
+Topics are popped after count, and data is the final item popped. Topics are recorded in the order they are popped.
+
+Unlike Logging, events have a consensus effect. An emitted event is attached to the execution receipt of the transaction,
+unless the context which emitted it is reverted, in which case the event is discarded.
+*/
+
+pub const EVENTS_FEATURE: u32 = 5;
+pub const EVENTS_FEATURE_VERSION: u32 = 1;
+/// The maximum number of topics which can be attached to a single event
+pub const MAX_EVENT_TOPICS: u8 = 4;
+
+#[derive(FromPrimitive)]
+pub enum EventFunctions{
+    Available = 0,
+    EmitEvent = 1
+}
+
+pub trait EventEmitter{
+    fn try_syscall(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<bool, NeutronError>{
+        if feature != EVENTS_FEATURE{
+            return Ok(false);
+        }
+        let f = num::FromPrimitive::from_u32(function);
+        if f.is_none(){
+            return Err(Recoverable(RecoverableError::InvalidSystemFunction));
+        }
+        let f=f.unwrap();
+        let result = match f{
+            EventFunctions::EmitEvent => {
+                self.emit_event(stack)
+            },
+            EventFunctions::Available => {
+                stack.push_sccs_u32(EVENTS_FEATURE_VERSION)
+            }
+        };
+        if result.is_err(){
+            Err(result.unwrap_err())
+        }else{
+            Ok(true)
+        }
+    }
+    fn emit_event(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+}
+
+/// Handler for registering the Events feature of a CallSystem with a SyscallDispatcher
+pub struct EventsFeature;
+
+impl<T: EventEmitter> FeatureHandler<T> for EventsFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        EventEmitter::try_syscall(host, stack, EVENTS_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
@@ -1,6 +1,10 @@
 use crate::callstack::*;
+use crate::encoding::to_hex;
+use crate::addressing::NeutronAddress;
+use crate::interface::LogLevel;
 use crate::neutronerror::*;
 use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
 /*
 ## Logging
 
@@ -8,10 +12,12 @@ ID: 2
 
 Functions:
 
+* available() -> (version: u32)
 * log_debug(count, string, ...)
 * log_info(count, string, ...)
 * log_warning(count, string, ...)
 * log_error(count, string, ...)
+* log_structured(level, count, key, value, ...)
 
 The exact order of printing messages is backward from what would be expected!
 This is designed so that no allocator is required for doing `println!` functions within neutron-star.
@@ -20,17 +26,142 @@ The expense of reordering the strings etc is a cost on the CallSystem. This coul
 but since logging is informative only and can easily be a no-op (other than needing to pop off appropriate number of stack items) this incurs no real risk.
 
 Note in neutron-star, log_info is used by default for println!
+
+log_structured lets the CallSystem do the formatting of values, so that contracts do not need to spend gas and code space on it.
+level is a single byte (0 = debug, 1 = info, 2 = warning, 3 = error) and count is a single byte specifying the number of fields.
+Unlike the other log functions, fields are popped in the order they are listed, ie, the contract must push the last field first.
+Each field is two items: a UTF8 key, and a value which begins with a single byte type tag followed by the value data:
+
+* 0: UTF8 string
+* 1: u32, 4 bytes little endian
+* 2: u64, 8 bytes little endian
+* 3: i64, 8 bytes little endian
+* 4: bytes, formatted as hex
+* 5: address, using the SCCS address encoding (little endian u32 version followed by the address data), formatted in the address text format
+
+Fields with an empty key are concatenated to form the message. Fields with a key are kept as key/value pairs alongside the message.
 */
 
-const LOGGING_FEATURE: u32 = 2;
+pub const LOGGING_FEATURE: u32 = 2;
+pub const LOGGING_FEATURE_VERSION: u32 = 2;
 
 #[derive(FromPrimitive)]
 pub enum LoggingFunctions{
-    Available = 0, //reserved??
+    Available = 0,
     LogDebug = 1,
     LogInfo,
     LogWarning,
-    LogError
+    LogError,
+    LogStructured
+}
+
+/// The type tags of log_structured values
+#[derive(FromPrimitive)]
+pub enum LogValueType{
+    Utf8 = 0,
+    U32,
+    U64,
+    I64,
+    Bytes,
+    Address
+}
+
+/// A log message decoded from the arguments of log_structured
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StructuredLog{
+    pub level: LogLevel,
+    pub message: String,
+    pub fields: Vec<(String, String)>
+}
+
+fn fixed_size(data: &[u8], size: usize) -> Result<&[u8], NeutronError>{
+    if data.len() < size{
+        return Err(Recoverable(RecoverableError::StackItemTooSmall));
+    }
+    if data.len() > size{
+        return Err(Recoverable(RecoverableError::StackItemTooLarge));
+    }
+    Ok(data)
+}
+
+/// Formats a single tagged log_structured value
+pub fn format_log_value(value: &[u8]) -> Result<String, NeutronError>{
+    if value.len() < 1{
+        return Err(Recoverable(RecoverableError::StackItemTooSmall));
+    }
+    let data = &value[1..];
+    let t = num::FromPrimitive::from_u8(value[0]);
+    if t.is_none(){
+        return Err(Recoverable(RecoverableError::InvalidTypeTag));
+    }
+    let formatted = match t.unwrap(){
+        LogValueType::Utf8 => {
+            String::from_utf8_lossy(data).to_string()
+        },
+        LogValueType::U32 => {
+            let mut b = [0u8; 4];
+            b.copy_from_slice(fixed_size(data, 4)?);
+            u32::from_le_bytes(b).to_string()
+        },
+        LogValueType::U64 => {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(fixed_size(data, 8)?);
+            u64::from_le_bytes(b).to_string()
+        },
+        LogValueType::I64 => {
+            let mut b = [0u8; 8];
+            b.copy_from_slice(fixed_size(data, 8)?);
+            i64::from_le_bytes(b).to_string()
+        },
+        LogValueType::Bytes => {
+            to_hex(data)
+        },
+        LogValueType::Address => {
+            if data.len() < 4{
+                return Err(Recoverable(RecoverableError::StackItemTooSmall));
+            }
+            let mut b = [0u8; 4];
+            b.copy_from_slice(&data[0..4]);
+            NeutronAddress{
+                version: u32::from_le_bytes(b),
+                data: data[4..].to_vec()
+            }.to_string()
+        }
+    };
+    Ok(formatted)
+}
+
+/// Pops and decodes the arguments of log_structured from the SCCS
+pub fn pop_structured_log(stack: &mut ContractCallStack) -> Result<StructuredLog, NeutronError>{
+    let level = stack.pop_sccs()?;
+    let count = stack.pop_sccs()?;
+    if level.len() < 1 || count.len() < 1{
+        return Err(Recoverable(RecoverableError::StackItemTooSmall));
+    }
+    if level.len() > 1 || count.len() > 1{
+        return Err(Recoverable(RecoverableError::StackItemTooLarge));
+    }
+    let level = match num::FromPrimitive::from_u8(level[0]){
+        None => {
+            return Err(Recoverable(RecoverableError::InvalidTypeTag));
+        },
+        Some(l) => l
+    };
+    let mut log = StructuredLog{
+        level: level,
+        message: String::default(),
+        fields: vec![]
+    };
+    for _i in 0..count[0]{
+        let key = stack.pop_sccs()?;
+        let value = format_log_value(&stack.pop_sccs()?)?;
+        if key.len() == 0{
+            log.message.push_str(&value);
+        }else{
+            log.fields.push((String::from_utf8_lossy(&key).to_string(), value));
+        }
+    }
+    Ok(log)
 }
 
 pub trait LoggingInterface{
@@ -55,9 +186,12 @@ pub trait LoggingInterface{
             },
             LoggingFunctions::LogError => {
                 self.log_error(stack)
+            },
+            LoggingFunctions::LogStructured => {
+                self.log_structured(stack)
             }
             LoggingFunctions::Available => {
-                Ok(())
+                stack.push_sccs_u32(LOGGING_FEATURE_VERSION)
             }
         };
         if result.is_err(){
@@ -70,5 +204,15 @@ pub trait LoggingInterface{
     fn log_info(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
     fn log_warning(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
     fn log_error(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn log_structured(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
 }
 
+/// Handler for registering the Logging feature of a CallSystem with a SyscallDispatcher
+pub struct LoggingFeature;
+
+impl<T: LoggingInterface> FeatureHandler<T> for LoggingFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        LoggingInterface::try_syscall(host, stack, LOGGING_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
@@ -1,2 +1,15 @@
+/*
+## Feature discovery
+
+Function 0 of every feature is `available() -> (version: u32)`. A CallSystem which implements the feature pushes the
+version of the feature it implements and returns successfully. Any call to a feature which the CallSystem does not
+implement, including function 0, results in an InvalidSystemFeature error.
+*/
+
+pub mod dispatch;
 pub mod storage;
-pub mod logging;
\ No newline at end of file
+pub mod logging;
+pub mod block_info;
+pub mod transaction_info;
+pub mod events;
+pub mod crypto;
\ No newline at end of file
@@ -1,6 +1,7 @@
 use crate::callstack::*;
 use crate::neutronerror::*;
 use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
 /*
 ## Global Storage
 
@@ -8,19 +9,27 @@ ID: 1
 
 Functions:
 
+* available() -> (version: u32)
 * store_state(key, value) -> ()
 * load_state(key) -> (value)
 * key_exists(key) -> (bool)
+* extend_rent(key, blocks: u32) -> (paid_until: u32)
+
+Every key has rent paid up to a block height. Reading a key whose rent has expired results in a StateOutOfRent error.
+extend_rent adds the specified number of blocks to the rent of an existing key which has not yet expired,
+and returns the block height up to which rent is now paid.
 */
 
-const GLOBAL_STORAGE_FEATURE: u32 = 1;
+pub const GLOBAL_STORAGE_FEATURE: u32 = 1;
+pub const GLOBAL_STORAGE_FEATURE_VERSION: u32 = 2;
 
 #[derive(FromPrimitive)]
 pub enum GlobalStorageFunctions{
-    Available = 0, //reserved??
+    Available = 0,
     StoreState = 1,
     LoadState,
-    KeyExists
+    KeyExists,
+    ExtendRent
 }
 
 pub trait GlobalStorage{
@@ -42,9 +51,12 @@ pub trait GlobalStorage{
             },
             GlobalStorageFunctions::StoreState => {
                 self.store_state(stack)
+            },
+            GlobalStorageFunctions::ExtendRent => {
+                self.extend_rent(stack)
             }
             GlobalStorageFunctions::Available => {
-                Ok(())
+                stack.push_sccs_u32(GLOBAL_STORAGE_FEATURE_VERSION)
             }
         };
         if result.is_err(){
@@ -56,5 +68,15 @@ pub trait GlobalStorage{
     fn store_state(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
     fn load_state(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
     fn key_exists(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn extend_rent(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
 }
 
+/// Handler for registering the Global Storage feature of a CallSystem with a SyscallDispatcher
+pub struct GlobalStorageFeature;
+
+impl<T: GlobalStorage> FeatureHandler<T> for GlobalStorageFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        GlobalStorage::try_syscall(host, stack, GLOBAL_STORAGE_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
@@ -0,0 +1,96 @@
+use crate::callstack::*;
+use crate::neutronerror::*;
+use crate::neutronerror::NeutronError::*;
+use crate::syscall_interfaces::dispatch::*;
+/*
+## Transaction Information
+
+ID: 4
+
+Functions:
+
+* available() -> (version: u32)
+* input_count() -> (u32)
+* output_count() -> (u32)
+* transaction_input(index: u32) -> (state, value: u64, sender: address)
+* transaction_output(index: u32) -> (state, value: u64, sender: address)
+* total_gas_fees() -> (u64)
+* total_fees() -> (u64)
+
+Integers and addresses use the same SCCS encoding as the Block Information feature.
+
+transaction_input and transaction_output push 3 items. After the call the sender address is the top item of the SCCS,
+followed by the value, and then the state attached to the item.
+Requesting an item which does not exist will result in an IndexOutOfRange error
+*/
+
+pub const TRANSACTION_INFO_FEATURE: u32 = 4;
+pub const TRANSACTION_INFO_FEATURE_VERSION: u32 = 1;
+
+#[derive(FromPrimitive)]
+pub enum TransactionInfoFunctions{
+    Available = 0,
+    InputCount = 1,
+    OutputCount,
+    TransactionInput,
+    TransactionOutput,
+    TotalGasFees,
+    TotalFees
+}
+
+pub trait TransactionInfo{
+    fn try_syscall(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<bool, NeutronError>{
+        if feature != TRANSACTION_INFO_FEATURE{
+            return Ok(false);
+        }
+        let f = num::FromPrimitive::from_u32(function);
+        if f.is_none(){
+            return Err(Recoverable(RecoverableError::InvalidSystemFunction));
+        }
+        let f=f.unwrap();
+        let result = match f{
+            TransactionInfoFunctions::InputCount => {
+                self.input_count(stack)
+            },
+            TransactionInfoFunctions::OutputCount => {
+                self.output_count(stack)
+            },
+            TransactionInfoFunctions::TransactionInput => {
+                self.transaction_input(stack)
+            },
+            TransactionInfoFunctions::TransactionOutput => {
+                self.transaction_output(stack)
+            },
+            TransactionInfoFunctions::TotalGasFees => {
+                self.total_gas_fees(stack)
+            },
+            TransactionInfoFunctions::TotalFees => {
+                self.total_fees(stack)
+            },
+            TransactionInfoFunctions::Available => {
+                stack.push_sccs_u32(TRANSACTION_INFO_FEATURE_VERSION)
+            }
+        };
+        if result.is_err(){
+            Err(result.unwrap_err())
+        }else{
+            Ok(true)
+        }
+    }
+    fn input_count(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn output_count(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn transaction_input(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn transaction_output(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn total_gas_fees(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+    fn total_fees(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>;
+}
+
+/// Handler for registering the Transaction Information feature of a CallSystem with a SyscallDispatcher
+pub struct TransactionInfoFeature;
+
+impl<T: TransactionInfo> FeatureHandler<T> for TransactionInfoFeature{
+    fn system_call(&self, host: &mut T, stack: &mut ContractCallStack, function: u32) -> Result<u32, NeutronError>{
+        TransactionInfo::try_syscall(host, stack, TRANSACTION_INFO_FEATURE, function)?;
+        Ok(0)
+    }
+}
\ No newline at end of file
@@ -2,24 +2,240 @@ extern crate neutron_star_constants;
 extern crate ring;
 extern crate struct_deser;
 extern crate elf;
+use neutron_star_constants::*;
 use crate::hypervisor::*;
 use crate::db::*;
-use std::path::PathBuf;
+use crate::merkle::StateProof;
+use crate::filedb::FileDB;
+use crate::snapshot::*;
+use crate::genesis::*;
+use std::collections::HashMap;
+use std::env;
+use std::error;
+use std::fmt;
+use std::io;
+use std::thread;
+use std::path::{Path, PathBuf};
 use crate::syscall_interfaces::storage;
 use crate::interface::*;
+use crate::addressing::*;
 use crate::callstack::*;
+use crate::logsinks::*;
 use crate::neutronerror::*;
 use crate::neutronerror::NeutronError::*;
 use crate::syscall_interfaces::logging;
+use crate::syscall_interfaces::block_info;
+use crate::syscall_interfaces::transaction_info;
+use crate::syscall_interfaces::events;
+use crate::syscall_interfaces::crypto;
+use crate::syscall_interfaces::dispatch::*;
+use std::rc::Rc;
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+/// The environment variable which sets the seed of every Testbench's random number generator, so that a failed test can be rerun with the same random values
+pub const TESTBENCH_SEED_VARIABLE: &str = "NEUTRON_TESTBENCH_SEED";
+/// The key within NEUTRONDB_ACCOUNT_SPACE holding the number of contracts an address has deployed, as a little endian u64
+pub const CONTRACT_NONCE_KEY: [u8; 2] = [NEUTRONDB_ACCOUNT_SPACE, 0];
 
 /// The Testbench is a virtual environment which can be used for testing smart contracts 
-#[derive(Default)]
 pub struct Testbench{
     pub transaction: TransactionContext,
-    pub db: ProtoDB
+    /// The block which contract executions are treated as being included in
+    pub block: BlockContext,
+    /// The coin balances of addresses, as given by genesis files. Nothing transfers coins yet
+    pub balances: HashMap<NeutronAddress, u64>,
+    /// The state database. An in-memory ProtoDB by default, or a FileDB for state which persists between runs
+    pub db: Box<dyn NeutronDB>,
+    /// The gas costs charged for system calls
+    pub gas_schedule: GasSchedule,
+    /// The number of blocks of rent given to newly written state. By default state never runs out of rent
+    pub rent_period: u32,
+    /// The largest user storage key which contracts may write. Unlimited by default
+    pub max_key_size: usize,
+    /// The largest user storage value which contracts may write. Unlimited by default
+    pub max_value_size: usize,
+    /// The system call features available to contracts. All built-in features are registered by default
+    pub syscalls: SyscallDispatcher<Testbench>,
+    /// The destinations of contract log messages and hypervisor diagnostics. By default everything is printed to stdout
+    pub log_sinks: LogSinks,
+    /// Events emitted by the execution currently in progress
+    events: Vec<NeutronEvent>,
+    /// Log messages emitted by contracts since the last call to clear_logs
+    logs: Vec<LogRecord>,
+    seed: u64,
+    /// The source of all random values used by the Testbench and its tests, such as random addresses
+    rng: StdRng
     //etc...
 }
 
+/// TESTBENCH_SEED_VARIABLE is set to something other than an unsigned 64 bit integer
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InvalidSeedError(pub String);
+
+impl fmt::Display for InvalidSeedError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{} must be an unsigned 64 bit integer, not {:?}", TESTBENCH_SEED_VARIABLE, self.0)
+    }
+}
+
+impl error::Error for InvalidSeedError{
+}
+
+/// An error creating a Testbench from a file or its environment
+#[derive(Debug)]
+pub enum TestbenchError{
+    Io(io::Error),
+    Snapshot(SnapshotError),
+    Genesis(GenesisError),
+    InvalidSeed(InvalidSeedError)
+}
+
+impl fmt::Display for TestbenchError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            TestbenchError::Io(e) => {
+                write!(f, "Testbench I/O error: {}", e)
+            },
+            TestbenchError::Snapshot(e) => {
+                write!(f, "{}", e)
+            },
+            TestbenchError::Genesis(e) => {
+                write!(f, "{}", e)
+            },
+            TestbenchError::InvalidSeed(e) => {
+                write!(f, "{}", e)
+            }
+        }
+    }
+}
+
+impl error::Error for TestbenchError{
+}
+
+impl From<io::Error> for TestbenchError{
+    fn from(e: io::Error) -> TestbenchError{
+        TestbenchError::Io(e)
+    }
+}
+
+impl From<SnapshotError> for TestbenchError{
+    fn from(e: SnapshotError) -> TestbenchError{
+        TestbenchError::Snapshot(e)
+    }
+}
+
+impl From<GenesisError> for TestbenchError{
+    fn from(e: GenesisError) -> TestbenchError{
+        TestbenchError::Genesis(e)
+    }
+}
+
+impl From<InvalidSeedError> for TestbenchError{
+    fn from(e: InvalidSeedError) -> TestbenchError{
+        TestbenchError::InvalidSeed(e)
+    }
+}
+
+/// The short address which the state of the given address is stored under
+fn short_address(address: &NeutronAddress) -> Result<NeutronShortAddress, NeutronError>{
+    match address.to_short_address(){
+        Err(_e) => {
+            Err(Recoverable(RecoverableError::InvalidAddress))
+        },
+        Ok(a) => {
+            Ok(a)
+        }
+    }
+}
+
+/// The seed given by TESTBENCH_SEED_VARIABLE, or None if it is not set
+pub fn environment_seed() -> Result<Option<u64>, InvalidSeedError>{
+    parse_seed(env::var(TESTBENCH_SEED_VARIABLE).ok())
+}
+
+fn parse_seed(value: Option<String>) -> Result<Option<u64>, InvalidSeedError>{
+    match value{
+        Some(v) => {
+            match v.trim().parse::<u64>(){
+                Ok(seed) => Ok(Some(seed)),
+                Err(_) => Err(InvalidSeedError(v))
+            }
+        },
+        None => {
+            Ok(None)
+        }
+    }
+}
+
+impl Default for Testbench{
+    /// Uses the seed given by TESTBENCH_SEED_VARIABLE if it is set, otherwise a new random seed.
+    /// Panics if the seed is invalid, use from_environment to handle it as an error instead
+    fn default() -> Testbench{
+        match Testbench::from_environment(){
+            Ok(testbench) => {
+                testbench
+            },
+            Err(e) => {
+                panic!("Invalid Testbench seed: {}", e);
+            }
+        }
+    }
+}
+
+impl Testbench{
+    /// Creates a Testbench whose random values are generated from the given seed, ignoring TESTBENCH_SEED_VARIABLE
+    pub fn with_seed(seed: u64) -> Testbench{
+        let mut syscalls = SyscallDispatcher::default();
+        syscalls.register(storage::GLOBAL_STORAGE_FEATURE, Rc::new(storage::GlobalStorageFeature));
+        syscalls.register(logging::LOGGING_FEATURE, Rc::new(logging::LoggingFeature));
+        syscalls.register(block_info::BLOCK_INFO_FEATURE, Rc::new(block_info::BlockInfoFeature));
+        syscalls.register(transaction_info::TRANSACTION_INFO_FEATURE, Rc::new(transaction_info::TransactionInfoFeature));
+        syscalls.register(events::EVENTS_FEATURE, Rc::new(events::EventsFeature));
+        syscalls.register(crypto::CRYPTO_FEATURE, Rc::new(crypto::CryptoFeature));
+        Testbench{
+            transaction: TransactionContext::default(),
+            block: BlockContext::default(),
+            balances: HashMap::new(),
+            db: Box::new(ProtoDB::default()),
+            gas_schedule: GasSchedule::default(),
+            rent_period: u32::max_value(),
+            max_key_size: usize::max_value(),
+            max_value_size: usize::max_value(),
+            syscalls: syscalls,
+            log_sinks: LogSinks::stdout(),
+            events: vec![],
+            logs: vec![],
+            seed: seed,
+            rng: StdRng::seed_from_u64(seed)
+        }
+    }
+}
+
+impl Drop for Testbench{
+    /// Prints the seed if a test fails while the Testbench is in use, so that the failure can be reproduced
+    fn drop(&mut self){
+        if thread::panicking(){
+            eprintln!("Testbench random seed: {} (set {}={} to reproduce)", self.seed, TESTBENCH_SEED_VARIABLE, self.seed);
+        }
+    }
+}
+
+/// The result of a top level execution within the Testbench
+#[derive(Clone, Debug, Eq, PartialEq, Default)]
+pub struct ExecutionReceipt{
+    /// The result returned by the VM
+    pub vm_result: NeutronVMResult,
+    /// The events emitted by the execution which were not reverted
+    pub events: Vec<NeutronEvent>,
+    /// The state root of the database after the execution was committed
+    pub state_root: [u8; 32],
+    /// The state read from and written to by the execution, excluding reverted checkpoints
+    pub state_differences: StateDifferences,
+    /// The storage usage after the execution of every address which the execution wrote to
+    pub storage_usage: HashMap<NeutronShortAddress, StorageUsage>
+}
+
 impl storage::GlobalStorage for Testbench{
     fn store_state(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
         let key = stack.pop_sccs()?;
@@ -35,6 +251,31 @@ impl storage::GlobalStorage for Testbench{
     fn key_exists(&mut self, _stack: &mut ContractCallStack) -> Result<(), NeutronError>{
         Err(Unrecoverable(UnrecoverableError::NotImplemented))
     }
+    fn extend_rent(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let key = stack.pop_sccs()?;
+        let blocks = stack.pop_sccs_u32()?;
+        let mut k = vec![NEUTRONDB_USER_SPACE];
+        k.extend_from_slice(&key);
+        let address = short_address(&stack.current_context().self_address)?;
+        //ensures the key exists and has not expired
+        self.read_state_key(stack, NEUTRONDB_USER_SPACE, &key)?;
+        let paid_until = match self.db.read_rent(&address, &k){
+            Err(_e) => {
+                return Err(Unrecoverable(UnrecoverableError::DatabaseWritingError));
+            },
+            Ok(None) => {
+                //state installed directly, such as by a genesis file, has no rent yet
+                self.block.height.saturating_add(blocks)
+            },
+            Ok(Some(v)) => {
+                v.saturating_add(blocks)
+            }
+        };
+        if self.db.write_rent(&address, &k, paid_until).is_err(){
+            return Err(Unrecoverable(UnrecoverableError::DatabaseWritingError));
+        }
+        stack.push_sccs_u32(paid_until)
+    }
 }
 impl Testbench{
     fn compile_log_message(&mut self, stack: &mut ContractCallStack) -> Result<String, NeutronError>{
@@ -59,53 +300,219 @@ impl Testbench{
         Ok(string)
     }
 }
-impl logging::LoggingInterface for Testbench{
-    fn log_debug(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+impl Testbench{
+    fn push_tx_item(stack: &mut ContractCallStack, item: Option<&TxItem>) -> Result<(), NeutronError>{
+        match item{
+            None => {
+                Err(Recoverable(RecoverableError::IndexOutOfRange))
+            },
+            Some(item) => {
+                stack.push_sccs(&item.state)?;
+                stack.push_sccs_u64(item.value)?;
+                stack.push_sccs_address(&item.sender)
+            }
+        }
+    }
+}
+impl Testbench{
+    fn record_log(&mut self, stack: &mut ContractCallStack, level: LogLevel) -> Result<(), NeutronError>{
         let msg = self.compile_log_message(stack)?;
-        (self as &mut dyn CallSystem).log_debug(&msg);
+        self.capture_log(stack, level, msg, vec![])
+    }
+    fn capture_log(&mut self, stack: &ContractCallStack, level: LogLevel, msg: String, fields: Vec<(String, String)>) -> Result<(), NeutronError>{
+        let mut text = msg.clone();
+        for (key, value) in fields.iter(){
+            text.push_str(&format!(" {}={}", key, value));
+        }
+        self.log_sinks.log(LogChannel::Contract, level, &text);
+        self.logs.push(LogRecord{
+            level: level,
+            message: msg,
+            address: stack.current_context().self_address.clone(),
+            nest_level: (stack.context_count()? as u32).saturating_sub(1),
+            fields: fields
+        });
         Ok(())
     }
+    /// All log messages captured since the Testbench was created or clear_logs was last called, in the order they were emitted
+    pub fn logs(&self) -> &[LogRecord]{
+        &self.logs
+    }
+    /// Discards all captured log messages
+    pub fn clear_logs(&mut self){
+        self.logs.clear();
+    }
+    /// Captured log messages which match the given predicate
+    pub fn filter_logs<F: Fn(&LogRecord) -> bool>(&self, predicate: F) -> Vec<&LogRecord>{
+        self.logs.iter().filter(|r| predicate(r)).collect()
+    }
+    /// Captured log messages of the given level
+    pub fn logs_at_level(&self, level: LogLevel) -> Vec<&LogRecord>{
+        self.filter_logs(|r| r.level == level)
+    }
+    /// Captured log messages emitted by the given contract address
+    pub fn logs_from(&self, address: &NeutronAddress) -> Vec<&LogRecord>{
+        self.filter_logs(|r| r.address == *address)
+    }
+}
+impl logging::LoggingInterface for Testbench{
+    fn log_debug(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        self.record_log(stack, LogLevel::Debug)
+    }
     fn log_info(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
-        let msg = self.compile_log_message(stack)?;
-        (self as &mut dyn CallSystem).log_info(&msg);
-        Ok(())
+        self.record_log(stack, LogLevel::Info)
     }
     fn log_warning(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
-        let msg = self.compile_log_message(stack)?;
-        (self as &mut dyn CallSystem).log_warning(&msg);
-        Ok(())
+        self.record_log(stack, LogLevel::Warning)
     }
     fn log_error(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
-        let msg = self.compile_log_message(stack)?;
-        (self as &mut dyn CallSystem).log_error(&msg);
-        Ok(())
+        self.record_log(stack, LogLevel::Error)
+    }
+    fn log_structured(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let log = logging::pop_structured_log(stack)?;
+        self.capture_log(stack, log.level, log.message, log.fields)
     }
 }
 
-impl CallSystem for Testbench{
-    fn system_call(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<u32, NeutronError>{
-        //go through each interface implementations until one returns true or an error occurs
-        if (self as &mut dyn storage::GlobalStorage).try_syscall(stack, feature, function)? == true{
-            return Ok(0);
+impl block_info::BlockInfo for Testbench{
+    fn block_creator(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_address(&self.block.creator)
+    }
+    fn block_gas_limit(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u64(self.block.gas_limit)
+    }
+    fn block_difficulty(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u64(self.block.difficulty)
+    }
+    fn block_height(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u32(self.block.height)
+    }
+    fn previous_block_time(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u64(self.block.previous_time)
+    }
+    fn block_hash(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let number = stack.pop_sccs_u32()?;
+        match self.block.previous_hashes.get(number as usize){
+            None => {
+                Err(Recoverable(RecoverableError::IndexOutOfRange))
+            },
+            Some(hash) => {
+                stack.push_sccs(hash)
+            }
         }
-        if (self as &mut dyn logging::LoggingInterface).try_syscall(stack, feature, function)? == true{
-            return Ok(0);
+    }
+}
+
+impl transaction_info::TransactionInfo for Testbench{
+    fn input_count(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u32(self.transaction.inputs.len() as u32)
+    }
+    fn output_count(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u32(self.transaction.outputs.len() as u32)
+    }
+    fn transaction_input(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let index = stack.pop_sccs_u32()?;
+        Testbench::push_tx_item(stack, self.transaction.inputs.get(index as usize))
+    }
+    fn transaction_output(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let index = stack.pop_sccs_u32()?;
+        Testbench::push_tx_item(stack, self.transaction.outputs.get(index as usize))
+    }
+    fn total_gas_fees(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u64(self.transaction.total_gas_fees)
+    }
+    fn total_fees(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        stack.push_sccs_u64(self.transaction.total_fees)
+    }
+}
+
+impl events::EventEmitter for Testbench{
+    fn emit_event(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let count = stack.pop_sccs()?;
+        if count.len() < 1{
+            return Err(Recoverable(RecoverableError::StackItemTooSmall));
+        }
+        if count.len() > 1 || count[0] > events::MAX_EVENT_TOPICS{
+            return Err(Recoverable(RecoverableError::StackItemTooLarge));
         }
+        let mut topics = vec![];
+        for _i in 0..count[0]{
+            topics.push(stack.pop_sccs()?);
+        }
+        let data = stack.pop_sccs()?;
+        self.events.push(NeutronEvent{
+            address: stack.current_context().self_address.clone(),
+            topics: topics,
+            data: data
+        });
+        Ok(())
+    }
+}
 
+impl crypto::CryptoInterface for Testbench{
+    fn sha256(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let data = stack.pop_sccs()?;
+        stack.charge_gas(self.gas_schedule.sha256_cost(data.len()));
+        stack.push_sccs(&crate::crypto::sha256(&data))
+    }
+    fn ripemd160(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let data = stack.pop_sccs()?;
+        stack.charge_gas(self.gas_schedule.ripemd160_cost(data.len()));
+        stack.push_sccs(&crate::crypto::ripemd160(&data))
+    }
+    fn double_sha256(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let data = stack.pop_sccs()?;
+        stack.charge_gas(self.gas_schedule.double_sha256_cost(data.len()));
+        stack.push_sccs(&crate::crypto::double_sha256(&data))
+    }
+    fn secp256k1_verify(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let hash = stack.pop_sccs()?;
+        let signature = stack.pop_sccs()?;
+        let public_key = stack.pop_sccs()?;
+        stack.charge_gas(self.gas_schedule.secp256k1_verify);
+        let valid = crate::crypto::secp256k1_verify(&hash, &signature, &public_key);
+        stack.push_sccs(&[valid as u8])
+    }
+    fn secp256k1_recover(&mut self, stack: &mut ContractCallStack) -> Result<(), NeutronError>{
+        let hash = stack.pop_sccs()?;
+        let signature = stack.pop_sccs()?;
+        stack.charge_gas(self.gas_schedule.secp256k1_recover);
+        match crate::crypto::secp256k1_recover(&hash, &signature){
+            None => {
+                Err(Recoverable(RecoverableError::InvalidSignature))
+            },
+            Some(public_key) => {
+                stack.push_sccs(&public_key)
+            }
+        }
+    }
+}
 
-        Ok(0)
+impl CallSystem for Testbench{
+    fn system_call(&mut self, stack: &mut ContractCallStack, feature: u32, function: u32) -> Result<u32, NeutronError>{
+        SyscallDispatcher::dispatch_owned(|t: &Testbench| &t.syscalls, self, stack, feature, function)
     }
     /// Get the current block height at execution
     /// Used to switch VM behavior in blockchain forks
     fn block_height(&self) -> Result<u32, NeutronError>{
-        Ok(1)
+        Ok(self.block.height)
+    }
+    fn log(&mut self, channel: LogChannel, level: LogLevel, msg: &str){
+        self.log_sinks.log(channel, level, msg);
     }
     /// Read a state key from the database using the permanent storage feature set
     /// Used for reading core contract bytecode by VMs
     fn read_state_key(&mut self, stack: &mut ContractCallStack, space: u8, key: &[u8]) -> Result<Vec<u8>, NeutronError>{
         let mut k = vec![space];
         k.extend_from_slice(key);
-        match self.db.read_key(&stack.current_context().self_address.to_short_address(), &k) {
+        let address = short_address(&stack.current_context().self_address)?;
+        match self.db.read_rent(&address, &k){
+            Ok(Some(paid_until)) if paid_until < self.block.height => {
+                return Err(Unrecoverable(UnrecoverableError::StateOutOfRent));
+            },
+            _ => {}
+        }
+        match self.db.read_key(&address, &k) {
             Err(_e) => {
                 Err(Unrecoverable(UnrecoverableError::StateOutOfRent))
             },
@@ -116,43 +523,286 @@ impl CallSystem for Testbench{
     }
     /// Write a state key to the database using the permanent storage feature set
     /// Used for writing bytecode etc by VMs
+    /// Keys which have no rent or whose rent has expired are given rent for rent_period blocks
     fn write_state_key(&mut self, stack: &mut ContractCallStack, space: u8, key: &[u8], value: &[u8]) -> Result<(), NeutronError>{
+        if space == NEUTRONDB_USER_SPACE && (key.len() > self.max_key_size || value.len() > self.max_value_size){
+            return Err(Recoverable(RecoverableError::StateItemTooLarge));
+        }
         let mut k = vec![space];
         k.extend_from_slice(key);
-        if self.db.write_key(&stack.current_context().self_address.to_short_address(), &k, value).is_err(){
-            Err(Unrecoverable(UnrecoverableError::DatabaseWritingError))
-        }else{
-            Ok(())
+        let address = short_address(&stack.current_context().self_address)?;
+        if self.db.write_key(&address, &k, value).is_err(){
+            return Err(Unrecoverable(UnrecoverableError::DatabaseWritingError));
         }
+        let needs_rent = match self.db.read_rent(&address, &k){
+            Ok(Some(paid_until)) => paid_until < self.block.height,
+            _ => true
+        };
+        if needs_rent && self.db.write_rent(&address, &k, self.block.height.saturating_add(self.rent_period)).is_err(){
+            return Err(Unrecoverable(UnrecoverableError::DatabaseWritingError));
+        }
+        Ok(())
+    }
+    /// Check whether a state key exists in the database, regardless of whether its rent has expired
+    /// Used for checking that a contract's bytecode is not overwritten by VMs
+    fn state_key_exists(&mut self, stack: &mut ContractCallStack, space: u8, key: &[u8]) -> Result<bool, NeutronError>{
+        let mut k = vec![space];
+        k.extend_from_slice(key);
+        let address = short_address(&stack.current_context().self_address)?;
+        Ok(self.db.read_key(&address, &k).is_ok())
     }
 }
 
 impl Testbench{
+    /// Creates a Testbench using the seed given by TESTBENCH_SEED_VARIABLE, or a new random seed if it is not set
+    pub fn from_environment() -> Result<Testbench, InvalidSeedError>{
+        match environment_seed()?{
+            Some(seed) => {
+                Ok(Testbench::with_seed(seed))
+            },
+            None => {
+                Ok(Testbench::with_seed(rand::thread_rng().gen()))
+            }
+        }
+    }
+    /// The seed of the random number generator
+    pub fn seed(&self) -> u64{
+        self.seed
+    }
+    /// The seeded random number generator, for tests which need random values other than addresses
+    pub fn rng(&mut self) -> &mut StdRng{
+        &mut self.rng
+    }
+    /// Creates an address with random data using the seeded random number generator
+    pub fn new_random_address(&mut self) -> NeutronAddress{
+        NeutronAddress::new_random_address(&mut self.rng)
+    }
+    /// Creates a Testbench whose state is persisted to the FileDB log at the given path, continuing from any state already in it
+    pub fn open_persistent<P: AsRef<Path>>(path: P) -> Result<Testbench, TestbenchError>{
+        let mut testbench = Testbench::from_environment()?;
+        testbench.db = Box::new(FileDB::open(path)?);
+        Ok(testbench)
+    }
+    /// Creates a Testbench containing the state of a genesis description, committed at the genesis block height
+    pub fn from_genesis(genesis: &Genesis) -> Result<Testbench, TestbenchError>{
+        let mut testbench = Testbench::from_environment()?;
+        genesis.install(&mut testbench)?;
+        Ok(testbench)
+    }
+    /// Creates a Testbench from a JSON genesis file. See the genesis module for the format
+    pub fn from_genesis_file<P: AsRef<Path>>(path: P) -> Result<Testbench, TestbenchError>{
+        Testbench::from_genesis(&Genesis::load(path)?)
+    }
+    /// Exports the committed state, balances and block context as a genesis description, which can be saved as JSON
+    pub fn export_genesis(&self) -> Genesis{
+        Genesis::from_testbench(self)
+    }
+    /// Creates a Testbench whose state is restored from a snapshot file saved by save_snapshot.
+    /// The block height continues from the height the snapshot's state was committed at
+    pub fn from_snapshot<P: AsRef<Path>>(path: P) -> Result<Testbench, TestbenchError>{
+        let snapshot = DBSnapshot::load(path)?;
+        let mut testbench = Testbench::from_environment()?;
+        testbench.db = Box::new(ProtoDB::from_snapshot(&snapshot)?);
+        testbench.block.height = snapshot.height;
+        Ok(testbench)
+    }
+    /// Saves all committed state, including deployed contracts, to a snapshot file so that it can be reused by other tests
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError>{
+        self.db.snapshot().save(path)
+    }
+    /// The number of contracts the given address has deployed, including uncommitted state
+    pub fn contract_nonce(&mut self, address: &NeutronAddress) -> Result<u64, NeutronError>{
+        match self.db.read_key(&short_address(address)?, &CONTRACT_NONCE_KEY){
+            Err(_e) => {
+                Ok(0)
+            },
+            Ok(v) => {
+                if v.len() != 8{
+                    return Err(Unrecoverable(UnrecoverableError::DatabaseCommitError));
+                }
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&v);
+                Ok(u64::from_le_bytes(bytes))
+            }
+        }
+    }
+    /// Derives the address of the next contract deployed by the given creator.
+    /// The creator's nonce is only used up once a deploy to this address executes, see execute_top_context
+    pub fn next_contract_address(&mut self, creator: &NeutronAddress) -> Result<NeutronAddress, NeutronError>{
+        let nonce = self.contract_nonce(creator)?;
+        Ok(NeutronAddress::contract_address(creator, nonce))
+    }
+    /// Increments the creator's nonce if the top context deploys to the creator's next contract address.
+    /// The nonce is written into the current checkpoint, so it is only kept if the deploy is committed
+    fn use_contract_nonce(&mut self, stack: &ContractCallStack) -> Result<(), NeutronError>{
+        let context = stack.current_context();
+        if context.execution_type != ExecutionType::Deploy{
+            return Ok(());
+        }
+        let nonce = self.contract_nonce(&context.sender)?;
+        if context.self_address != NeutronAddress::contract_address(&context.sender, nonce){
+            return Ok(());
+        }
+        let next = nonce.saturating_add(1).to_le_bytes();
+        if self.db.write_key(&short_address(&context.sender)?, &CONTRACT_NONCE_KEY, &next).is_err(){
+            return Err(Unrecoverable(UnrecoverableError::DatabaseWritingError));
+        }
+        Ok(())
+    }
+    /// Creates a top level deploy context using the next contract address of the sender. The context stack MUST be empty
+    pub fn create_top_level_deploy(&mut self, stack: &mut ContractCallStack, sender: NeutronAddress, gas_limit: u64, value: u64) -> Result<NeutronAddress, NeutronError>{
+        let address = self.next_contract_address(&sender)?;
+        stack.create_top_level_deploy(address.clone(), sender, gas_limit, value)?;
+        Ok(address)
+    }
+    /// Creates a top level deploy context at an address derived from a salt and code hash rather than the sender's nonce,
+    /// so that the address can be known before deployment. The context stack MUST be empty
+    pub fn create_top_level_deploy_with_salt(&mut self, stack: &mut ContractCallStack, sender: NeutronAddress, salt: &[u8; 32], code_hash: &[u8; 32], gas_limit: u64, value: u64) -> Result<NeutronAddress, NeutronError>{
+        let address = NeutronAddress::contract_address_from_salt(&sender, salt, code_hash);
+        stack.create_top_level_deploy(address.clone(), sender, gas_limit, value)?;
+        Ok(address)
+    }
+    /// Creates a nested deploy context using the next contract address of the currently executing contract. The context stack MUST NOT be empty
+    pub fn create_deploy(&mut self, stack: &mut ContractCallStack, gas_limit: u64, value: u64) -> Result<NeutronAddress, NeutronError>{
+        let creator = stack.current_context().self_address.clone();
+        let address = self.next_contract_address(&creator)?;
+        stack.create_deploy(address.clone(), gas_limit, value)?;
+        Ok(address)
+    }
     /// Begins execution using the top context within the stack
-    pub fn execute_top_context(&mut self, stack: &mut ContractCallStack) -> Result<NeutronVMResult, NeutronError>{
+    /// State written and events emitted by the execution are discarded if it fails or signals that it should be reverted,
+    /// including the use of the creator's nonce by a deploy
+    pub fn execute_top_context(&mut self, stack: &mut ContractCallStack) -> Result<ExecutionReceipt, NeutronError>{
+        let address = &stack.current_context().self_address;
+        if address.validate().is_err(){
+            return Err(Recoverable(RecoverableError::InvalidAddress));
+        }
+        let kind = address.kind();
         self.db.checkpoint().unwrap();
-        if stack.current_context().self_address.version == 2 {
-            let mut vm = X86Interface::new(self, stack);
-            match vm.execute(){
-                Err(e) => {
-                    self.db.clear_checkpoints();
-                    return Err(e);
-                },
-                Ok(v) => {
-                    if self.db.commit().is_err(){
+        self.events.clear();
+        if let Err(e) = self.use_contract_nonce(stack){
+            self.db.clear_checkpoints();
+            return Err(e);
+        }
+        match kind{
+            AddressKind::X86Contract => {
+                let mut vm = X86Interface::new(self, stack);
+                match vm.execute(){
+                    Err(e) => {
                         self.db.clear_checkpoints();
-                        return Err(Unrecoverable(UnrecoverableError::DatabaseCommitError));
+                        self.events.clear();
+                        return Err(e);
+                    },
+                    Ok(v) => {
+                        return self.complete_execution(v);
                     }
-                    return Ok(v);
                 }
+            },
+            _ => {
+                self.db.clear_checkpoints();
+                return Err(Unrecoverable(UnrecoverableError::UnknownVM));
             }
-        }else{
-            return Err(Unrecoverable(UnrecoverableError::UnknownVM));
         }
     }
     
+    /// Commits the state of an execution which completed, or discards its state and events if it signaled that it should be reverted
+    fn complete_execution(&mut self, result: NeutronVMResult) -> Result<ExecutionReceipt, NeutronError>{
+        if result.should_revert{
+            self.db.clear_checkpoints();
+            self.events.clear();
+            return Ok(ExecutionReceipt{
+                vm_result: result,
+                state_root: self.db.state_root(),
+                ..ExecutionReceipt::default()
+            });
+        }
+        let differences = match self.db.compute_state_differences(){
+            Err(_e) => {
+                self.db.clear_checkpoints();
+                self.events.clear();
+                return Err(Unrecoverable(UnrecoverableError::DatabaseCommitError));
+            },
+            Ok(d) => {
+                d
+            }
+        };
+        self.db.set_block_height(self.block.height);
+        if self.db.commit().is_err(){
+            self.db.clear_checkpoints();
+            self.events.clear();
+            return Err(Unrecoverable(UnrecoverableError::DatabaseCommitError));
+        }
+        let storage_usage = differences.writes.keys().map(|a| (*a, self.db.storage_usage(a))).collect();
+        Ok(ExecutionReceipt{
+            vm_result: result,
+            events: self.events.drain(..).collect(),
+            state_root: self.db.state_root(),
+            state_differences: differences,
+            storage_usage: storage_usage
+        })
+    }
+    
+    /// Generates a proof of a contract's user storage key against the current state root, for use in fixtures etc
+    pub fn prove_user_state(&self, address: &NeutronAddress, key: &[u8]) -> Result<StateProof, NeutronError>{
+        let mut k = vec![NEUTRONDB_USER_SPACE];
+        k.extend_from_slice(key);
+        match self.db.prove_key(&short_address(address)?, &k){
+            Err(_e) => {
+                Err(Unrecoverable(UnrecoverableError::DatabaseCommitError))
+            },
+            Ok(p) => {
+                Ok(p)
+            }
+        }
+    }
+    
+    /// Starts keeping the history of state from the current block onwards so that it can be read using read_user_state_at
+    pub fn enable_history(&mut self, pruning: HistoryPruning){
+        self.db.set_block_height(self.block.height);
+        self.db.enable_history(pruning);
+    }
+    /// Reads a contract's user storage key as it was after the executions of the given block height.
+    /// History must first be enabled using enable_history, otherwise HistoryNotEnabled is returned.
+    /// Returns IndexOutOfRange if the height is earlier than the history kept, or None if the key did not exist at that height
+    pub fn read_user_state_at(&self, address: &NeutronAddress, key: &[u8], height: u32) -> Result<Option<Vec<u8>>, NeutronError>{
+        let mut k = vec![NEUTRONDB_USER_SPACE];
+        k.extend_from_slice(key);
+        match self.db.earliest_history_height(){
+            None => {
+                return Err(Unrecoverable(UnrecoverableError::HistoryNotEnabled));
+            },
+            Some(earliest) if height < earliest => {
+                return Err(Recoverable(RecoverableError::IndexOutOfRange));
+            },
+            _ => {}
+        }
+        match self.db.read_key_at(&short_address(address)?, &k, height){
+            Err(_e) => {
+                Err(Unrecoverable(UnrecoverableError::DatabaseCommitError))
+            },
+            Ok(v) => {
+                Ok(v)
+            }
+        }
+    }
+
+    /// Lists a contract's user storage keys starting with the given prefix along with their values, ordered by key.
+    /// The keys are given without the user space prefix, the same as a contract would see them
+    pub fn scan_user_state(&self, address: &NeutronAddress, prefix: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>, NeutronError>{
+        let mut k = vec![NEUTRONDB_USER_SPACE];
+        k.extend_from_slice(prefix);
+        match self.db.scan_keys(&short_address(address)?, &k){
+            Err(_e) => {
+                Err(Unrecoverable(UnrecoverableError::DatabaseCommitError))
+            },
+            Ok(keys) => {
+                Ok(keys.into_iter().map(|(key, value)| (key[1..].to_vec(), value)).collect())
+            }
+        }
+    }
+
     /// Deploy a smart contract from an ELF executable file
-    pub fn deploy_from_elf(&mut self, stack: &mut ContractCallStack, file: String) -> Result<NeutronVMResult, NeutronError>{
+    pub fn deploy_from_elf(&mut self, stack: &mut ContractCallStack, file: String) -> Result<ExecutionReceipt, NeutronError>{
         assert!(stack.context_count()? == 1, "Exactly one context should be pushed to the ContractCallStack");
         let path = PathBuf::from(file);
         let file = elf::File::open_path(&path).unwrap();
@@ -175,8 +825,517 @@ impl Testbench{
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use crate::syscall_interfaces::block_info::*;
+    use crate::syscall_interfaces::transaction_info::*;
+    use crate::syscall_interfaces::events::*;
+    use crate::syscall_interfaces::crypto::*;
+    use crate::syscall_interfaces::storage::*;
+    use crate::syscall_interfaces::logging::*;
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_block_info(){
+        let mut testbench = Testbench::default();
+        testbench.block.height = 120;
+        testbench.block.gas_limit = 1000;
+        testbench.block.creator = NeutronAddress{version: 1, data: vec![5, 6, 7]};
+        testbench.block.previous_hashes = vec![[1; 32], [2; 32]];
+        let mut stack = ContractCallStack::default();
+        assert_eq!(testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::BlockHeight as u32).unwrap(), 0);
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 120);
+        assert_eq!((&testbench as &dyn CallSystem).block_height().unwrap(), 120);
+        testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::BlockGasLimit as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u64().unwrap(), 1000);
+        testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::BlockCreator as u32).unwrap();
+        assert_eq!(stack.pop_sccs().unwrap(), vec![1, 0, 0, 0, 5, 6, 7]);
+
+        stack.push_sccs_u32(1).unwrap();
+        testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::BlockHash as u32).unwrap();
+        assert_eq!(stack.pop_sccs().unwrap(), vec![2; 32]);
+        stack.push_sccs_u32(2).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::BlockHash as u32),
+            Err(Recoverable(RecoverableError::IndexOutOfRange)));
+    }
+    #[test]
+    fn test_transaction_info(){
+        let mut testbench = Testbench::default();
+        let payer = NeutronAddress{version: 1, data: vec![1; 20]};
+        let contract = NeutronAddress{version: 2, data: vec![2; 20]};
+        testbench.transaction
+            .add_input(payer.clone(), 5000, &[])
+            .add_input(payer.clone(), 700, &[])
+            .add_output(contract.clone(), 4000, &[9, 9])
+            .set_fees(1000, 1700);
+        let mut stack = ContractCallStack::default();
+        testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::InputCount as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 2);
+        testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::OutputCount as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 1);
+
+        stack.push_sccs_u32(1).unwrap();
+        testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::TransactionInput as u32).unwrap();
+        let mut expected = vec![1, 0, 0, 0];
+        expected.extend_from_slice(&[1; 20]);
+        assert_eq!(stack.pop_sccs().unwrap(), expected);
+        assert_eq!(stack.pop_sccs_u64().unwrap(), 700);
+        assert_eq!(stack.pop_sccs().unwrap(), Vec::<u8>::new());
+
+        stack.push_sccs_u32(0).unwrap();
+        testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::TransactionOutput as u32).unwrap();
+        stack.drop_sccs().unwrap();
+        assert_eq!(stack.pop_sccs_u64().unwrap(), 4000);
+        assert_eq!(stack.pop_sccs().unwrap(), vec![9, 9]);
+
+        stack.push_sccs_u32(1).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::TransactionOutput as u32),
+            Err(Recoverable(RecoverableError::IndexOutOfRange)));
+        testbench.system_call(&mut stack, TRANSACTION_INFO_FEATURE, TransactionInfoFunctions::TotalFees as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u64().unwrap(), 1700);
+        assert_eq!(stack.sccs_item_count().unwrap(), 0);
+    }
+    #[test]
+    fn test_emit_event(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        let contract = NeutronAddress{version: 2, data: vec![2; 20]};
+        stack.create_top_level_call(contract.clone(), testbench.new_random_address(), 10000, 0);
+        stack.push_sccs(&[10, 20]).unwrap(); //data
+        stack.push_sccs(&[2]).unwrap(); //topic 2
+        stack.push_sccs(&[1]).unwrap(); //topic 1
+        stack.push_sccs(&[2]).unwrap(); //count
+        testbench.system_call(&mut stack, EVENTS_FEATURE, EventFunctions::EmitEvent as u32).unwrap();
+        assert_eq!(stack.sccs_item_count().unwrap(), 0);
+        assert_eq!(testbench.events, vec![NeutronEvent{
+            address: contract,
+            topics: vec![vec![1], vec![2]],
+            data: vec![10, 20]
+        }]);
+
+        stack.push_sccs(&[]).unwrap();
+        stack.push_sccs(&[MAX_EVENT_TOPICS + 1]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, EVENTS_FEATURE, EventFunctions::EmitEvent as u32),
+            Err(Recoverable(RecoverableError::StackItemTooLarge)));
+        assert_eq!(testbench.events.len(), 1);
+    }
+    #[test]
+    fn test_crypto_gas(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        stack.push_sccs(&[1; 40]).unwrap();
+        testbench.system_call(&mut stack, CRYPTO_FEATURE, CryptoFunctions::Sha256 as u32).unwrap();
+        assert_eq!(stack.pop_sccs().unwrap(), crate::crypto::sha256(&[1; 40]).to_vec());
+        assert_eq!(stack.take_pending_gas(), testbench.gas_schedule.sha256_base + 2 * testbench.gas_schedule.sha256_per_word);
+
+        stack.push_sccs(&[]).unwrap();
+        stack.push_sccs(&[0; 64]).unwrap();
+        stack.push_sccs(&[0; 32]).unwrap();
+        testbench.system_call(&mut stack, CRYPTO_FEATURE, CryptoFunctions::Secp256k1Verify as u32).unwrap();
+        assert_eq!(stack.pop_sccs().unwrap(), vec![0]);
+        assert_eq!(stack.take_pending_gas(), testbench.gas_schedule.secp256k1_verify);
+    }
+    #[test]
+    fn test_feature_discovery(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::Available as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), GLOBAL_STORAGE_FEATURE_VERSION);
+        testbench.system_call(&mut stack, CRYPTO_FEATURE, CryptoFunctions::Available as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), CRYPTO_FEATURE_VERSION);
+        assert_eq!(testbench.system_call(&mut stack, 0xFFFF, 0), Err(Recoverable(RecoverableError::InvalidSystemFeature)));
+        assert_eq!(testbench.system_call(&mut stack, 0xFFFF, 1), Err(Recoverable(RecoverableError::InvalidSystemFeature)));
+        assert_eq!(stack.sccs_item_count().unwrap(), 0);
+    }
+    #[test]
+    fn test_syscall_dispatcher(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        assert!(testbench.syscalls.disable(BLOCK_INFO_FEATURE).is_some());
+        assert_eq!(testbench.system_call(&mut stack, BLOCK_INFO_FEATURE, BlockInfoFunctions::Available as u32),
+            Err(Recoverable(RecoverableError::InvalidSystemFeature)));
+        assert!(!testbench.syscalls.register(CRYPTO_FEATURE, Rc::new(BlockInfoFeature)));
+        //remap block information to a new ID
+        assert!(testbench.syscalls.register(100, Rc::new(BlockInfoFeature)));
+        testbench.block.height = 5;
+        testbench.system_call(&mut stack, 100, BlockInfoFunctions::BlockHeight as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 5);
+
+        let handler = |t: &mut Testbench, s: &mut ContractCallStack, function: u32| -> Result<u32, NeutronError>{
+            s.push_sccs_u32(t.block.height + function)?;
+            Ok(7)
+        };
+        assert!(testbench.syscalls.override_feature(100, Rc::new(handler)).is_some());
+        assert_eq!(testbench.system_call(&mut stack, 100, 1).unwrap(), 7);
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 6);
+    }
+    #[test]
+    fn test_log_capture(){
+        let mut testbench = Testbench::default();
+        testbench.log_sinks.clear();
+        let mut stack = ContractCallStack::default();
+        let contract = NeutronAddress{version: 2, data: vec![2; 20]};
+        let other = NeutronAddress{version: 2, data: vec![3; 20]};
+        stack.create_top_level_call(contract.clone(), testbench.new_random_address(), 10000, 0);
+        stack.push_sccs(b"hello ").unwrap();
+        stack.push_sccs(b"world").unwrap();
+        stack.push_sccs(&[2]).unwrap();
+        testbench.system_call(&mut stack, LOGGING_FEATURE, LoggingFunctions::LogInfo as u32).unwrap();
+        stack.create_call(other.clone(), 1000, 0);
+        stack.push_sccs(b"failed").unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        testbench.system_call(&mut stack, LOGGING_FEATURE, LoggingFunctions::LogError as u32).unwrap();
+
+        assert_eq!(testbench.logs().len(), 2);
+        assert_eq!(testbench.logs()[0], LogRecord{
+            level: LogLevel::Info,
+            message: "hello world".to_string(),
+            address: contract.clone(),
+            nest_level: 0,
+            fields: vec![]
+        });
+        let errors = testbench.logs_at_level(LogLevel::Error);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].message, "failed");
+        assert_eq!(errors[0].nest_level, 1);
+        assert_eq!(testbench.logs_from(&other).len(), 1);
+        assert_eq!(testbench.filter_logs(|r| r.message.contains("hello")).len(), 1);
+        testbench.clear_logs();
+        assert!(testbench.logs().is_empty());
+    }
+    #[test]
+    fn test_structured_log(){
+        let mut testbench = Testbench::default();
+        let memory = MemorySink::default();
+        testbench.log_sinks.clear();
+        testbench.log_sinks.add(Box::new(memory.clone()), LogLevel::Debug, None);
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_call(testbench.new_random_address(), testbench.new_random_address(), 10000, 0);
+        //fields are pushed last first
+        stack.push_sccs(&[LogValueType::Bytes as u8, 0xAB, 0x01]).unwrap();
+        stack.push_sccs(b"data").unwrap();
+        let mut balance = vec![LogValueType::I64 as u8];
+        balance.extend_from_slice(&(-5i64).to_le_bytes());
+        stack.push_sccs(&balance).unwrap();
+        stack.push_sccs(b"balance").unwrap();
+        let mut value = vec![LogValueType::U32 as u8];
+        value.extend_from_slice(&42u32.to_le_bytes());
+        stack.push_sccs(&value).unwrap();
+        stack.push_sccs(b"").unwrap();
+        stack.push_sccs(&[LogValueType::Utf8 as u8, b'x', b'=']).unwrap();
+        stack.push_sccs(b"").unwrap();
+        stack.push_sccs(&[4]).unwrap(); //count
+        stack.push_sccs(&[LogLevel::Warning as u8]).unwrap();
+        testbench.system_call(&mut stack, LOGGING_FEATURE, LoggingFunctions::LogStructured as u32).unwrap();
+        assert_eq!(stack.sccs_item_count().unwrap(), 0);
+
+        let log = &testbench.logs()[0];
+        assert_eq!(log.level, LogLevel::Warning);
+        assert_eq!(log.message, "x=42");
+        assert_eq!(log.fields, vec![("balance".to_string(), "-5".to_string()), ("data".to_string(), "ab01".to_string())]);
+        assert_eq!(memory.entries()[0].message, "x=42 balance=-5 data=ab01");
+
+        stack.push_sccs(&[9]).unwrap();
+        stack.push_sccs(b"").unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        stack.push_sccs(&[LogLevel::Info as u8]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, LOGGING_FEATURE, LoggingFunctions::LogStructured as u32),
+            Err(Recoverable(RecoverableError::InvalidTypeTag)));
+    }
+    #[test]
+    fn test_rent_expiry(){
+        let mut testbench = Testbench::default();
+        testbench.rent_period = 10;
+        testbench.block.height = 100;
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_call(testbench.new_random_address(), testbench.new_random_address(), 10000, 0);
+        testbench.db.checkpoint().unwrap();
+        stack.push_sccs(&[5]).unwrap(); //value
+        stack.push_sccs(&[1]).unwrap(); //key
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+
+        testbench.block.height = 110;
+        stack.push_sccs(&[1]).unwrap();
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::LoadState as u32).unwrap();
+        assert_eq!(stack.pop_sccs().unwrap(), vec![5]);
+
+        stack.push_sccs_u32(5).unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::ExtendRent as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 115);
+
+        testbench.block.height = 116;
+        stack.push_sccs(&[1]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::LoadState as u32),
+            Err(Unrecoverable(UnrecoverableError::StateOutOfRent)));
+        stack.push_sccs_u32(5).unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::ExtendRent as u32),
+            Err(Unrecoverable(UnrecoverableError::StateOutOfRent)));
+
+        //state without any rent is only extended from the current block
+        let address = stack.current_context().self_address.to_short_address().unwrap();
+        testbench.db.write_key(&address, &[NEUTRONDB_USER_SPACE, 2], &[6]).unwrap();
+        stack.push_sccs_u32(5).unwrap();
+        stack.push_sccs(&[2]).unwrap();
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::ExtendRent as u32).unwrap();
+        assert_eq!(stack.pop_sccs_u32().unwrap(), 121);
+    }
+    #[test]
+    fn test_storage_limits(){
+        let mut testbench = Testbench::default();
+        testbench.max_key_size = 2;
+        testbench.max_value_size = 3;
+        let mut stack = ContractCallStack::default();
+        let address = testbench.new_random_address();
+        stack.create_top_level_call(address.clone(), testbench.new_random_address(), 10000, 0);
+        testbench.db.checkpoint().unwrap();
+        stack.push_sccs(&[5, 5, 5]).unwrap(); //value
+        stack.push_sccs(&[1, 1]).unwrap(); //key
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+        stack.push_sccs(&[5, 5, 5, 5]).unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32),
+            Err(Recoverable(RecoverableError::StateItemTooLarge)));
+        stack.push_sccs(&[5]).unwrap();
+        stack.push_sccs(&[1, 1, 1]).unwrap();
+        assert_eq!(testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32),
+            Err(Recoverable(RecoverableError::StateItemTooLarge)));
+        //the key includes the user space prefix
+        assert_eq!(testbench.db.storage_usage(&address.to_short_address().unwrap()), StorageUsage{
+            keys: 1,
+            bytes: 6
+        });
+    }
+    #[test]
+    fn test_contract_addresses(){
+        let mut testbench = Testbench::default();
+        let sender = NeutronAddress{version: 1, data: vec![1; 20]};
+        let mut stack = ContractCallStack::default();
+        let contract = testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap();
+        assert_eq!(contract, NeutronAddress::contract_address(&sender, 0));
+        assert_eq!(stack.current_context().self_address, contract);
+        assert_eq!(stack.current_context().execution_type, ExecutionType::Deploy);
+
+        //contracts deployed by other contracts use the deploying contract's own nonce
+        let nested = testbench.create_deploy(&mut stack, 5000, 0).unwrap();
+        assert_eq!(nested, NeutronAddress::contract_address(&contract, 0));
+        assert_eq!(stack.current_context().sender, contract);
+        assert_eq!(stack.current_context().origin, sender);
+        assert_eq!(testbench.create_deploy(&mut stack, 5000, 0).unwrap(), NeutronAddress::contract_address(&nested, 0));
+
+        //the nonce is only used up by a deploy which is committed
+        let mut stack = ContractCallStack::default();
+        assert_eq!(testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap(), contract);
+        testbench.db.checkpoint().unwrap();
+        testbench.use_contract_nonce(&stack).unwrap();
+        testbench.complete_execution(NeutronVMResult::default()).unwrap();
+        assert_eq!(testbench.contract_nonce(&sender).unwrap(), 1);
+        let mut stack = ContractCallStack::default();
+        assert_eq!(testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap(), NeutronAddress::contract_address(&sender, 1));
+
+        //as is a reverted one
+        testbench.db.checkpoint().unwrap();
+        testbench.use_contract_nonce(&stack).unwrap();
+        let mut result = NeutronVMResult::default();
+        result.should_revert = true;
+        testbench.complete_execution(result).unwrap();
+        assert_eq!(testbench.contract_nonce(&sender).unwrap(), 1);
+
+        //deploys to salted addresses do not use the nonce at all
+        let mut stack = ContractCallStack::default();
+        let salted = testbench.create_top_level_deploy_with_salt(&mut stack, sender.clone(), &[3; 32], &[4; 32], 10000, 0).unwrap();
+        assert_eq!(salted, NeutronAddress::contract_address_from_salt(&sender, &[3; 32], &[4; 32]));
+        testbench.db.checkpoint().unwrap();
+        testbench.use_contract_nonce(&stack).unwrap();
+        testbench.complete_execution(NeutronVMResult::default()).unwrap();
+        assert_eq!(testbench.contract_nonce(&sender).unwrap(), 1);
+    }
+    #[test]
+    fn test_vm_dispatch(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_call(NeutronAddress{version: 2, data: vec![1; 19]}, testbench.new_random_address(), 10000, 0);
+        assert_eq!(testbench.execute_top_context(&mut stack).unwrap_err(), Recoverable(RecoverableError::InvalidAddress));
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_call(NeutronAddress{version: 1, data: vec![1; 20]}, testbench.new_random_address(), 10000, 0);
+        assert_eq!(testbench.execute_top_context(&mut stack).unwrap_err(), Unrecoverable(UnrecoverableError::UnknownVM));
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_call(NeutronAddress{version: 50, data: vec![1; 32]}, testbench.new_random_address(), 10000, 0);
+        assert_eq!(testbench.execute_top_context(&mut stack).unwrap_err(), Unrecoverable(UnrecoverableError::UnknownVM));
+    }
+    #[test]
+    fn test_seeded_addresses(){
+        let mut a = Testbench::with_seed(5);
+        let mut b = Testbench::with_seed(5);
+        assert_eq!(a.seed(), 5);
+        let address = a.new_random_address();
+        assert_eq!(address, b.new_random_address());
+        assert!(a.new_random_address() != address);
+        b.new_random_address();
+        assert!(Testbench::with_seed(6).new_random_address() != address);
+        assert_eq!(a.rng().gen::<u64>(), b.rng().gen::<u64>());
+    }
+    #[test]
+    fn test_snapshot_roundtrip(){
+        let mut path = std::env::temp_dir();
+        path.push(format!("neutron-testbench-{}-snapshot", std::process::id()));
+        let mut testbench = Testbench::default();
+        testbench.block.height = 30;
+        let mut stack = ContractCallStack::default();
+        let address = testbench.new_random_address();
+        stack.create_top_level_call(address.clone(), testbench.new_random_address(), 10000, 0);
+        testbench.db.checkpoint().unwrap();
+        stack.push_sccs(&[5]).unwrap(); //value
+        stack.push_sccs(&[1]).unwrap(); //key
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+        testbench.db.set_block_height(testbench.block.height);
+        testbench.db.commit().unwrap();
+        testbench.save_snapshot(&path).unwrap();
+
+        let restored = Testbench::from_snapshot(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(restored.block.height, 30);
+        assert_eq!(restored.db.state_root(), testbench.db.state_root());
+        assert_eq!(restored.scan_user_state(&address, &[]).unwrap(), vec![(vec![1], vec![5])]);
+        match Testbench::from_snapshot(&path){
+            Err(TestbenchError::Snapshot(SnapshotError::Io(_))) => {},
+            _ => {
+                panic!("expected a snapshot I/O error");
+            }
+        }
+    }
+    #[test]
+    fn test_contract_nonce_persistence(){
+        let mut path = std::env::temp_dir();
+        path.push(format!("neutron-testbench-{}-nonces", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let sender = NeutronAddress{version: 1, data: vec![1; 20]};
+        {
+            let mut testbench = Testbench::open_persistent(&path).unwrap();
+            let mut stack = ContractCallStack::default();
+            testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap();
+            testbench.db.checkpoint().unwrap();
+            testbench.use_contract_nonce(&stack).unwrap();
+            testbench.complete_execution(NeutronVMResult::default()).unwrap();
+        }
+        let mut testbench = Testbench::open_persistent(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(testbench.contract_nonce(&sender).unwrap(), 1);
+        let mut stack = ContractCallStack::default();
+        assert_eq!(testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap(), NeutronAddress::contract_address(&sender, 1));
+
+        //nonces are also kept by genesis files
+        let rebuilt = Testbench::from_genesis(&testbench.export_genesis());
+        assert_eq!(rebuilt.unwrap().contract_nonce(&sender).unwrap(), 1);
+    }
+    #[test]
+    fn test_deploy_to_existing_contract(){
+        let mut testbench = Testbench::default();
+        let sender = NeutronAddress{version: 1, data: vec![1; 20]};
+        let contract = NeutronAddress::contract_address(&sender, 0);
+        testbench.db.checkpoint().unwrap();
+        testbench.db.write_key(&contract.to_short_address().unwrap(), &[X86Interface::X86_SPACE, X86Interface::CODE_SECTION_SPACE, 0], &[0x90]).unwrap();
+        testbench.db.commit().unwrap();
+        let root = testbench.db.state_root();
+        let mut stack = ContractCallStack::default();
+        stack.create_top_level_deploy(contract.clone(), sender.clone(), 10000, 0).unwrap();
+        assert_eq!(testbench.execute_top_context(&mut stack).unwrap_err(), Recoverable(RecoverableError::ContractAlreadyExists));
+        assert_eq!(testbench.db.state_root(), root);
+        //the failed deploy does not use up the sender's nonce
+        assert_eq!(testbench.contract_nonce(&sender).unwrap(), 0);
+
+        //code whose rent has expired still belongs to the contract
+        testbench.rent_period = 10;
+        testbench.block.height = 100;
+        testbench.db.checkpoint().unwrap();
+        testbench.db.write_rent(&contract.to_short_address().unwrap(), &[X86Interface::X86_SPACE, X86Interface::CODE_SECTION_SPACE, 0], 50).unwrap();
+        testbench.db.commit().unwrap();
+        let root = testbench.db.state_root();
+        assert_eq!(testbench.read_state_key(&mut stack, X86Interface::X86_SPACE, &[X86Interface::CODE_SECTION_SPACE, 0]),
+            Err(Unrecoverable(UnrecoverableError::StateOutOfRent)));
+        assert_eq!(testbench.state_key_exists(&mut stack, X86Interface::X86_SPACE, &[X86Interface::CODE_SECTION_SPACE, 0]), Ok(true));
+        assert_eq!(testbench.execute_top_context(&mut stack).unwrap_err(), Recoverable(RecoverableError::ContractAlreadyExists));
+        assert_eq!(testbench.db.state_root(), root);
+    }
+    #[test]
+    fn test_deploy_to_non_contract_address(){
+        let mut testbench = Testbench::default();
+        let sender = testbench.new_random_address();
+        let mut stack = ContractCallStack::default();
+        assert_eq!(stack.create_top_level_deploy(sender.clone(), sender.clone(), 10000, 0), Err(Recoverable(RecoverableError::InvalidAddress)));
+        assert_eq!(stack.context_count().unwrap(), 0);
+        let contract = testbench.create_top_level_deploy(&mut stack, sender.clone(), 10000, 0).unwrap();
+        assert_eq!(stack.create_deploy(sender.clone(), 5000, 0), Err(Recoverable(RecoverableError::InvalidAddress)));
+        assert_eq!(stack.current_context().self_address, contract);
+    }
+    #[test]
+    fn test_parse_seed(){
+        assert_eq!(parse_seed(None), Ok(None));
+        assert_eq!(parse_seed(Some(" 42 ".to_string())), Ok(Some(42)));
+        assert_eq!(parse_seed(Some("abc".to_string())), Err(InvalidSeedError("abc".to_string())));
+        assert_eq!(parse_seed(Some("-1".to_string())), Err(InvalidSeedError("-1".to_string())));
+        //fallible constructors report an invalid seed alongside their other errors
+        match TestbenchError::from(InvalidSeedError("abc".to_string())){
+            TestbenchError::InvalidSeed(_) => {},
+            _ => {
+                panic!("expected an invalid seed error");
+            }
+        }
+    }
+    #[test]
+    fn test_reverted_execution(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        let address = testbench.new_random_address();
+        stack.create_top_level_call(address.clone(), testbench.new_random_address(), 10000, 0);
+        let root = testbench.db.state_root();
+        testbench.db.checkpoint().unwrap();
+        stack.push_sccs(&[5]).unwrap(); //value
+        stack.push_sccs(&[1]).unwrap(); //key
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+        stack.push_sccs(&[1]).unwrap(); //data
+        stack.push_sccs(&[0]).unwrap(); //topic count
+        testbench.system_call(&mut stack, EVENTS_FEATURE, EventFunctions::EmitEvent as u32).unwrap();
+        let mut result = NeutronVMResult::default();
+        result.should_revert = true;
+        let receipt = testbench.complete_execution(result).unwrap();
+        assert!(receipt.vm_result.should_revert);
+        assert!(receipt.events.is_empty());
+        assert!(receipt.state_differences.writes.is_empty());
+        assert_eq!(receipt.state_root, root);
+        assert_eq!(testbench.db.state_root(), root);
+        assert!(testbench.scan_user_state(&address, &[]).unwrap().is_empty());
+
+        testbench.db.checkpoint().unwrap();
+        stack.push_sccs(&[5]).unwrap();
+        stack.push_sccs(&[1]).unwrap();
+        testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+        let receipt = testbench.complete_execution(NeutronVMResult::default()).unwrap();
+        assert!(receipt.state_root != root);
+        assert_eq!(testbench.scan_user_state(&address, &[]).unwrap(), vec![(vec![1], vec![5])]);
+    }
+    #[test]
+    fn test_state_history(){
+        let mut testbench = Testbench::default();
+        let mut stack = ContractCallStack::default();
+        let address = testbench.new_random_address();
+        stack.create_top_level_call(address.clone(), testbench.new_random_address(), 10000, 0);
+        assert_eq!(testbench.read_user_state_at(&address, &[1], 0), Err(Unrecoverable(UnrecoverableError::HistoryNotEnabled)));
+        testbench.block.height = 10;
+        testbench.enable_history(HistoryPruning::KeepBlocks(2));
+        for height in 11..15{
+            testbench.block.height = height;
+            testbench.db.checkpoint().unwrap();
+            stack.push_sccs(&[height as u8]).unwrap(); //value
+            stack.push_sccs(&[1]).unwrap(); //key
+            testbench.system_call(&mut stack, GLOBAL_STORAGE_FEATURE, GlobalStorageFunctions::StoreState as u32).unwrap();
+            testbench.complete_execution(NeutronVMResult::default()).unwrap();
+        }
+        assert_eq!(testbench.read_user_state_at(&address, &[1], 11), Err(Recoverable(RecoverableError::IndexOutOfRange)));
+        assert_eq!(testbench.read_user_state_at(&address, &[1], 12), Ok(Some(vec![12])));
+        assert_eq!(testbench.read_user_state_at(&address, &[1], 14), Ok(Some(vec![14])));
+        assert_eq!(testbench.read_user_state_at(&address, &[2], 14), Ok(None));
+    }
+}